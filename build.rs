@@ -1,9 +1,10 @@
 extern crate gl_generator;
+extern crate wayland_scanner;
 
 use gl_generator::{Registry, Api, Profile, Fallbacks};
 use std::env;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     let target = env::var("TARGET").unwrap();
@@ -51,9 +52,25 @@ fn main() {
                           "EGL_EXT_platform_device",
                       ])
             .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
+
+        let mut file = File::create(&dest.join("egl_extra_bindings.rs")).unwrap();
+        Registry::new(Api::Egl, (1, 5), Profile::Core, Fallbacks::All, [
+                          "EGL_KHR_swap_buffers_with_damage",
+                          "EGL_EXT_swap_buffers_with_damage",
+                          "EGL_KHR_partial_update",
+                          "EGL_EXT_buffer_age",
+                      ])
+            .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
     }
 
     if target.contains("linux") || target.contains("dragonfly") || target.contains("freebsd") || target.contains("openbsd") {
+        // the Wayland protocols that wayland-client doesn't provide
+        let protocols = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("protocols");
+        println!("cargo:rerun-if-changed={}", protocols.join("viewporter.xml").display());
+        wayland_scanner::generate(wayland_scanner::Action::Interfaces,
+                                  protocols.join("viewporter.xml"),
+                                  dest.join("viewporter_interfaces.rs"));
+
         let mut file = File::create(&dest.join("glx_bindings.rs")).unwrap();
         Registry::new(Api::Glx, (1, 4), Profile::Core, Fallbacks::All, [])
             .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
@@ -63,13 +80,17 @@ fn main() {
                           "GLX_ARB_create_context",
                           "GLX_ARB_create_context_profile",
                           "GLX_ARB_create_context_robustness",
+                          "GLX_EXT_create_context_es2_profile",
+                          "GLX_EXT_create_context_es_profile",
                           "GLX_ARB_context_flush_control",
                           "GLX_ARB_fbconfig_float",
                           "GLX_ARB_framebuffer_sRGB",
                           "GLX_EXT_framebuffer_sRGB",
                           "GLX_ARB_multisample",
                           "GLX_EXT_swap_control",
-                          "GLX_SGI_swap_control"
+                          "GLX_SGI_swap_control",
+                          "GLX_EXT_buffer_age",
+                          "GLX_OML_sync_control",
                       ])
             .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
 
@@ -89,6 +110,15 @@ fn main() {
                           "EGL_EXT_platform_device",
                       ])
             .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
+
+        let mut file = File::create(&dest.join("egl_extra_bindings.rs")).unwrap();
+        Registry::new(Api::Egl, (1, 5), Profile::Core, Fallbacks::All, [
+                          "EGL_KHR_swap_buffers_with_damage",
+                          "EGL_EXT_swap_buffers_with_damage",
+                          "EGL_KHR_partial_update",
+                          "EGL_EXT_buffer_age",
+                      ])
+            .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
     }
 
     if target.contains("android") {
@@ -108,6 +138,15 @@ fn main() {
                           "EGL_EXT_platform_device",
                       ])
             .write_bindings(gl_generator::StaticStructGenerator, &mut file).unwrap();
+
+        let mut file = File::create(&dest.join("egl_extra_bindings.rs")).unwrap();
+        Registry::new(Api::Egl, (1, 5), Profile::Core, Fallbacks::All, [
+                          "EGL_KHR_swap_buffers_with_damage",
+                          "EGL_EXT_swap_buffers_with_damage",
+                          "EGL_KHR_partial_update",
+                          "EGL_EXT_buffer_age",
+                      ])
+            .write_bindings(gl_generator::StructGenerator, &mut file).unwrap();
     }
 
     if target.contains("ios") {
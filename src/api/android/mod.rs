@@ -5,6 +5,7 @@ extern crate android_glue;
 use CreationError::{self, OsError};
 
 use winit;
+use window::build_winit_window;
 
 use Api;
 use ContextError;
@@ -31,12 +32,17 @@ pub struct PlatformSpecificHeadlessBuilderAttributes;
 
 impl Window {
     pub fn new(
-        _: &WindowAttributes,
+        window: &WindowAttributes,
         pf_reqs: &PixelFormatRequirements,
         opengl: &GlAttributes<&Window>,
         _: &PlatformSpecificWindowBuilderAttributes,
-        ozkriff_window: &winit::Window,
-    ) -> Result<Window, CreationError> {
+        winit_window: Option<winit::Window>,
+    ) -> Result<(Window, winit::Window), CreationError> {
+        let winit_window = match winit_window {
+            Some(w) => w,
+            None => try!(build_winit_window(window, winit::WindowBuilder::new())),
+        };
+
         let opengl = opengl.clone().map_sharing(|w| &w.context);
         let native_window = unsafe { android_glue::get_native_window() };
         if native_window.is_null() {
@@ -48,9 +54,11 @@ impl Window {
             &opengl,
             egl::NativeDisplay::Android
         ).and_then(|p| p.finish(native_window as *const _)));
-        Ok(Window {
+        let window = Window {
             context: context,
-        })
+        };
+
+        Ok((window, winit_window))
     }
 }
 
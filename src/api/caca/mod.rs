@@ -16,6 +16,7 @@ use CursorState;
 use MouseCursor;
 use WindowAttributes;
 
+use std::cell::Cell;
 use std::collections::VecDeque;
 use std::path::Path;
 use std::ptr;
@@ -26,7 +27,8 @@ pub struct Window {
     libcaca: ffi::LibCaca,
     display: *mut ffi::caca_display_t,
     opengl: OsMesaContext,
-    dither: *mut ffi::caca_dither_t,
+    // recreated when the context is resized, as it has the dimensions of the buffer
+    dither: Cell<*mut ffi::caca_dither_t>,
 }
 
 #[derive(Clone)]
@@ -120,18 +122,7 @@ impl Window {
             return Err(CreationError::OsError("caca_create_display failed".to_string()));
         }
 
-        let dither = unsafe {
-            #[cfg(target_endian = "little")]
-            fn get_masks() -> (u32, u32, u32, u32) { (0xff, 0xff00, 0xff0000, 0xff000000) }
-            #[cfg(target_endian = "big")]
-            fn get_masks() -> (u32, u32, u32, u32) { (0xff000000, 0xff0000, 0xff00, 0xff) }
-
-            let masks = get_masks();
-            (libcaca.caca_create_dither)(32, opengl_dimensions.0 as libc::c_int,
-                                         opengl_dimensions.1 as libc::c_int,
-                                         opengl_dimensions.0 as libc::c_int * 4,
-                                         masks.0, masks.1, masks.2, masks.3)
-        };
+        let dither = create_dither(&libcaca, opengl_dimensions);
 
         if dither.is_null() {
             unsafe { (libcaca.caca_free_display)(display) };
@@ -142,7 +133,7 @@ impl Window {
             libcaca: libcaca,
             display: display,
             opengl: opengl,
-            dither: dither,
+            dither: Cell::new(dither),
         })
     }
 
@@ -177,9 +168,11 @@ impl Window {
         self.get_inner_size()
     }
 
+    /// The size of the terminal can't be changed, so only the buffer that is rendered to is
+    /// resized.
     #[inline]
-    pub fn set_inner_size(&self, _x: u32, _y: u32) {
-        unimplemented!()
+    pub fn set_inner_size(&self, x: u32, y: u32) {
+        self.resize_surface(x, y)
     }
 
     #[inline]
@@ -263,11 +256,12 @@ impl GlContext for Window {
             let width = (self.libcaca.caca_get_canvas_width)(canvas);
             let height = (self.libcaca.caca_get_canvas_height)(canvas);
 
-            let buffer = self.opengl.get_framebuffer().chunks(self.opengl.get_dimensions().0 as usize)
+            let row_width = self.opengl.get_dimensions().0 as usize;
+            let buffer = self.opengl.get_framebuffer().chunks(row_width)
                                     .flat_map(|i| i.iter().cloned()).rev().collect::<Vec<u32>>();
 
             (self.libcaca.caca_dither_bitmap)(canvas, 0, 0, width as libc::c_int,
-                                              height as libc::c_int, self.dither,
+                                              height as libc::c_int, self.dither.get(),
                                               buffer.as_ptr() as *const _);
             (self.libcaca.caca_refresh_display)(self.display);
         };
@@ -275,6 +269,17 @@ impl GlContext for Window {
         Ok(())
     }
 
+    fn resize_surface(&self, width: u32, height: u32) {
+        let dither = create_dither(&self.libcaca, (width, height));
+        if dither.is_null() {
+            return;
+        }
+
+        self.opengl.resize_surface(width, height);
+        unsafe { (self.libcaca.caca_free_dither)(self.dither.get()) };
+        self.dither.set(dither);
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         self.opengl.get_api()
@@ -290,8 +295,24 @@ impl Drop for Window {
     #[inline]
     fn drop(&mut self) {
         unsafe {
-            (self.libcaca.caca_free_dither)(self.dither);
+            (self.libcaca.caca_free_dither)(self.dither.get());
             (self.libcaca.caca_free_display)(self.display);
         }
     }
 }
+
+/// Creates the dither that converts the content of a buffer of `dimensions` for the terminal.
+fn create_dither(libcaca: &ffi::LibCaca, dimensions: (u32, u32)) -> *mut ffi::caca_dither_t {
+    #[cfg(target_endian = "little")]
+    fn get_masks() -> (u32, u32, u32, u32) { (0xff, 0xff00, 0xff0000, 0xff000000) }
+    #[cfg(target_endian = "big")]
+    fn get_masks() -> (u32, u32, u32, u32) { (0xff000000, 0xff0000, 0xff00, 0xff) }
+
+    let masks = get_masks();
+    unsafe {
+        (libcaca.caca_create_dither)(32, dimensions.0 as libc::c_int,
+                                     dimensions.1 as libc::c_int,
+                                     dimensions.0 as libc::c_int * 4,
+                                     masks.0, masks.1, masks.2, masks.3)
+    }
+}
@@ -1,4 +1,5 @@
 
+use Api;
 use CreationError;
 use GlAttributes;
 use GlProfile;
@@ -15,6 +16,25 @@ pub fn build_nsattributes<T>(pf_reqs: &PixelFormatRequirements, opengl: &GlAttri
         // Note: we are not using ranges because of a rust bug that should be fixed here:
         // https://github.com/rust-lang/rust/pull/27050
 
+        (GlRequest::AtLeast(Api::OpenGl, min_version), _, profile) |
+        (GlRequest::AtLeastGlThenGles { opengl_version: min_version, .. }, _, profile) => {
+            let (ns_profile, version) = if profile == Some(GlProfile::Compatibility) {
+                (NSOpenGLProfileVersionLegacy as u32, (2, 1))
+            } else if NSAppKitVersionNumber.floor() >= NSAppKitVersionNumber10_9 {
+                (NSOpenGLProfileVersion4_1Core as u32, (4, 1))
+            } else if NSAppKitVersionNumber.floor() >= NSAppKitVersionNumber10_7 {
+                (NSOpenGLProfileVersion3_2Core as u32, (3, 2))
+            } else {
+                (NSOpenGLProfileVersionLegacy as u32, (2, 1))
+            };
+
+            if version < min_version {
+                return Err(CreationError::OpenGlVersionNotSupported);
+            }
+
+            ns_profile
+        },
+
         (GlRequest::Latest, _, Some(GlProfile::Compatibility)) => NSOpenGLProfileVersionLegacy as u32,
         (GlRequest::Latest, _, _) => {
             if NSAppKitVersionNumber.floor() >= NSAppKitVersionNumber10_9 {
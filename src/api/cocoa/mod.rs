@@ -5,6 +5,7 @@ use CreationError::OsError;
 
 use winit;
 use winit::os::macos::WindowExt;
+use window::build_winit_window;
 
 use ContextError;
 use GlAttributes;
@@ -55,17 +56,18 @@ impl Window {
                pf_reqs: &PixelFormatRequirements,
                opengl: &GlAttributes<&Window>,
                _pl_attribs: &PlatformSpecificWindowBuilderAttributes,
-               winit_window: &winit::Window)
-               -> Result<Window, CreationError>
+               winit_window: Option<winit::Window>)
+               -> Result<(Window, winit::Window), CreationError>
     {
+        let winit_window = match winit_window {
+            Some(w) => w,
+            None => try!(build_winit_window(win_attribs, winit::WindowBuilder::new())),
+        };
+
         if opengl.sharing.is_some() {
             unimplemented!()
         }
 
-        // not implemented
-        assert!(win_attribs.min_dimensions.is_none());
-        assert!(win_attribs.max_dimensions.is_none());
-
         match opengl.robustness {
             Robustness::RobustNoResetNotification | Robustness::RobustLoseContextOnReset => {
                 return Err(CreationError::RobustnessNotSupported);
@@ -85,7 +87,7 @@ impl Window {
             pixel_format: pf,
         };
 
-        Ok(window)
+        Ok((window, winit_window))
     }
 
     fn create_context(view: id, pf_reqs: &PixelFormatRequirements, opengl: &GlAttributes<&Window>)
@@ -191,6 +193,12 @@ impl GlContext for Window {
         Ok(())
     }
 
+    #[inline]
+    fn resize_surface(&self, _width: u32, _height: u32) {
+        // the context takes the new size of its view into account
+        unsafe { self.context.update(); }
+    }
+
     #[inline]
     fn get_api(&self) -> ::Api {
         ::Api::OpenGl
@@ -1,11 +1,23 @@
 #![cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
 #![allow(dead_code)]
 
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::{Arc, Mutex, Weak};
 use std::os::raw::{c_void, c_char, c_int};
 
 pub const RTLD_LAZY: c_int = 0x001;
 pub const RTLD_NOW: c_int = 0x002;
 
+/// Environment variable with the paths of the EGL libraries to try first, separated with `:`.
+pub const EGL_LIBRARY_VAR: &'static str = "GLUTIN_EGL_LIBRARY";
+
+/// The EGL libraries that are tried by default, on X11 as well as on Wayland. Under GLVND,
+/// `libEGL.so.1` is the vendor-neutral one.
+pub const EGL_LIBRARIES: &'static [&'static str] = &["libEGL.so.1", "libEGL.so"];
+
 #[link="dl"]
 extern {
     pub fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
@@ -13,3 +25,111 @@ extern {
     pub fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
     pub fn dlclose(handle: *mut c_void) -> c_int;
 }
+
+lazy_static! {
+    // The libraries that are currently open, by path. Opening the same path twice returns the
+    // same `Library`, so that the handle is only closed once nothing uses it anymore.
+    static ref LIBRARIES: Mutex<HashMap<String, Weak<Library>>> = Mutex::new(HashMap::new());
+}
+
+/// A library opened with `dlopen`, which is closed when the last `Arc` pointing to it is dropped.
+pub struct Library {
+    handle: *mut c_void,
+    path: String,
+}
+
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    /// Opens the library at `path`, or returns the one that is already open.
+    ///
+    /// `path` can be a file name, in which case the usual search path of the dynamic linker is
+    /// used, or a path to a file.
+    pub fn open(path: &str) -> Result<Arc<Library>, String> {
+        let mut libraries = LIBRARIES.lock().unwrap();
+
+        if let Some(library) = libraries.get(path).and_then(|l| l.upgrade()) {
+            return Ok(library);
+        }
+
+        let c_path = try!(CString::new(path).map_err(|_| format!("Invalid path: {:?}", path)));
+        let handle = unsafe { dlopen(c_path.as_ptr(), RTLD_NOW) };
+        if handle.is_null() {
+            return Err(last_error());
+        }
+
+        let library = Arc::new(Library { handle: handle, path: path.to_owned() });
+        libraries.insert(path.to_owned(), Arc::downgrade(&library));
+        Ok(library)
+    }
+
+    /// Opens the first library of `paths` that can be loaded.
+    pub fn open_first<S: AsRef<str>>(paths: &[S]) -> Option<Arc<Library>> {
+        for path in paths {
+            match Library::open(path.as_ref()) {
+                Ok(library) => {
+                    info!("Loaded {}", library.path());
+                    return Some(library);
+                },
+                Err(err) => debug!("Failed to load {}: {}", path.as_ref(), err),
+            }
+        }
+        None
+    }
+
+    /// Returns the path that was used to open the library.
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the address of `symbol`, or null if the library doesn't export it.
+    pub fn sym(&self, symbol: &str) -> *const c_void {
+        let symbol = match CString::new(symbol) {
+            Ok(s) => s,
+            Err(_) => return ptr::null(),
+        };
+        unsafe { dlsym(self.handle, symbol.as_ptr()) as *const _ }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        let mut libraries = LIBRARIES.lock().unwrap();
+
+        // another thread may have opened the same path again in the meantime
+        let reopened = libraries.get(&self.path).map(|l| l.upgrade().is_some()).unwrap_or(false);
+        if !reopened {
+            libraries.remove(&self.path);
+        }
+
+        unsafe { dlclose(self.handle); }
+    }
+}
+
+/// Returns the libraries to try to load, in order: the paths of the environment variable `var`
+/// (separated with `:`), then the `configured` paths, then the `defaults`.
+pub fn candidates(var: &str, configured: &[String], defaults: &[&str]) -> Vec<String> {
+    let mut paths = Vec::new();
+
+    if let Some(value) = env::var_os(var) {
+        paths.extend(env::split_paths(&value).filter_map(|p| p.to_str().map(|p| p.to_owned()))
+                                               .filter(|p| !p.is_empty()));
+    }
+
+    paths.extend(configured.iter().cloned());
+    paths.extend(defaults.iter().map(|&p| p.to_owned()));
+    paths
+}
+
+fn last_error() -> String {
+    unsafe {
+        let err = dlerror();
+        if err.is_null() {
+            "Unknown error".to_owned()
+        } else {
+            CStr::from_ptr(err).to_string_lossy().into_owned()
+        }
+    }
+}
@@ -6,15 +6,25 @@ use ContextError;
 use CreationError;
 use GlAttributes;
 use GlContext;
+use GlProfile;
 use GlRequest;
 use PixelFormat;
 use PixelFormatRequirements;
+use Rect;
 use ReleaseBehavior;
+use ResetStatus;
 use Robustness;
 use Api;
+use Backend;
+use CreationAttempt;
 
+use robustness::ResetStatusQuery;
+use versions;
+
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_void, c_int};
+use std::sync::Mutex;
 use std::{mem, ptr};
 
 pub mod ffi;
@@ -35,13 +45,24 @@ pub enum NativeDisplay {
     Other(Option<ffi::EGLNativeDisplayType>),
 }
 
+lazy_static! {
+    // the number of contexts that use each display. EGL returns the same display for the same
+    // native display, and `eglTerminate` destroys the resources of all of them, so a display is
+    // only terminated along with the last context that uses it
+    static ref DISPLAY_USERS: Mutex<HashMap<usize, usize>> = Mutex::new(HashMap::new());
+}
+
 pub struct Context {
     egl: ffi::egl::Egl,
+    extra_functions: ffi::egl_extra::Egl,
+    extensions: Vec<String>,
     display: ffi::egl::types::EGLDisplay,
     context: ffi::egl::types::EGLContext,
     surface: ffi::egl::types::EGLSurface,
     api: Api,
+    version: Option<(u8, u8)>,
     pixel_format: PixelFormat,
+    reset_status: ResetStatusQuery,
 }
 
 #[cfg(target_os = "android")]
@@ -161,9 +182,21 @@ impl Context {
     /// This function initializes some things and chooses the pixel format.
     ///
     /// To finish the process, you must call `.finish(window)` on the `ContextPrototype`.
+    #[inline]
     pub fn new<'a>(egl: ffi::egl::Egl, pf_reqs: &PixelFormatRequirements,
                    opengl: &'a GlAttributes<&'a Context>, native_display: NativeDisplay)
                    -> Result<ContextPrototype<'a>, CreationError>
+    {
+        Context::new_with_visual_filter(egl, pf_reqs, opengl, native_display, &|_| true)
+    }
+
+    /// Same as `new`, but only the configs whose native visual ID is accepted by `visual_filter`
+    /// are considered.
+    pub fn new_with_visual_filter<'a>(egl: ffi::egl::Egl, pf_reqs: &PixelFormatRequirements,
+                                      opengl: &'a GlAttributes<&'a Context>,
+                                      native_display: NativeDisplay,
+                                      visual_filter: &Fn(ffi::egl::types::EGLint) -> bool)
+                                      -> Result<ContextPrototype<'a>, CreationError>
     {
         if opengl.sharing.is_some() {
             unimplemented!()
@@ -199,19 +232,19 @@ impl Context {
         };
 
         // binding the right API and choosing the version
-        let (version, api) = unsafe {
+        let (version, min_version, api) = unsafe {
             match opengl.version {
                 GlRequest::Latest => {
                     if egl_version >= (1, 4) {
                         if egl.BindAPI(ffi::egl::OPENGL_API) != 0 {
-                            (None, Api::OpenGl)
+                            (None, None, Api::OpenGl)
                         } else if egl.BindAPI(ffi::egl::OPENGL_ES_API) != 0 {
-                            (None, Api::OpenGlEs)
+                            (None, None, Api::OpenGlEs)
                         } else {
                             return Err(CreationError::OpenGlVersionNotSupported);
                         }
                     } else {
-                        (None, Api::OpenGlEs)
+                        (None, None, Api::OpenGlEs)
                     }
                 },
                 GlRequest::Specific(Api::OpenGlEs, version) => {
@@ -220,7 +253,7 @@ impl Context {
                             return Err(CreationError::OpenGlVersionNotSupported);
                         }
                     }
-                    (Some(version), Api::OpenGlEs)
+                    (Some(version), None, Api::OpenGlEs)
                 },
                 GlRequest::Specific(Api::OpenGl, version) => {
                     if egl_version < (1, 4) {
@@ -229,27 +262,60 @@ impl Context {
                     if egl.BindAPI(ffi::egl::OPENGL_API) == 0 {
                         return Err(CreationError::OpenGlVersionNotSupported);
                     }
-                    (Some(version), Api::OpenGl)
+                    (Some(version), None, Api::OpenGl)
                 },
                 GlRequest::Specific(_, _) => return Err(CreationError::OpenGlVersionNotSupported),
+                GlRequest::AtLeast(Api::OpenGlEs, min_version) => {
+                    if egl_version >= (1, 2) {
+                        if egl.BindAPI(ffi::egl::OPENGL_ES_API) == 0 {
+                            return Err(CreationError::OpenGlVersionNotSupported);
+                        }
+                    }
+                    (None, Some(min_version), Api::OpenGlEs)
+                },
+                GlRequest::AtLeast(Api::OpenGl, min_version) => {
+                    if egl_version < (1, 4) {
+                        return Err(CreationError::OpenGlVersionNotSupported);
+                    }
+                    if egl.BindAPI(ffi::egl::OPENGL_API) == 0 {
+                        return Err(CreationError::OpenGlVersionNotSupported);
+                    }
+                    (None, Some(min_version), Api::OpenGl)
+                },
+                GlRequest::AtLeast(_, _) => return Err(CreationError::OpenGlVersionNotSupported),
                 GlRequest::GlThenGles { opengles_version, opengl_version } => {
                     if egl_version >= (1, 4) {
                         if egl.BindAPI(ffi::egl::OPENGL_API) != 0 {
-                            (Some(opengl_version), Api::OpenGl)
+                            (Some(opengl_version), None, Api::OpenGl)
                         } else if egl.BindAPI(ffi::egl::OPENGL_ES_API) != 0 {
-                            (Some(opengles_version), Api::OpenGlEs)
+                            (Some(opengles_version), None, Api::OpenGlEs)
                         } else {
                             return Err(CreationError::OpenGlVersionNotSupported);
                         }
                     } else {
-                        (Some(opengles_version), Api::OpenGlEs)
+                        (Some(opengles_version), None, Api::OpenGlEs)
+                    }
+                },
+                GlRequest::AtLeastGlThenGles { opengl_version, opengles_version } => {
+                    if egl_version >= (1, 4) {
+                        if egl.BindAPI(ffi::egl::OPENGL_API) != 0 {
+                            (None, Some(opengl_version), Api::OpenGl)
+                        } else if egl.BindAPI(ffi::egl::OPENGL_ES_API) != 0 {
+                            (None, Some(opengles_version), Api::OpenGlEs)
+                        } else {
+                            return Err(CreationError::OpenGlVersionNotSupported);
+                        }
+                    } else {
+                        (None, Some(opengles_version), Api::OpenGlEs)
                     }
                 },
             }
         };
 
+        // the config must at least support the minimum version
         let (config_id, pixel_format) = unsafe {
-            try!(choose_fbconfig(&egl, display, &egl_version, api, version, pf_reqs))
+            try!(choose_fbconfig(&egl, display, &egl_version, api, version.or(min_version),
+                                 pf_reqs, visual_filter))
         };
 
         Ok(ContextPrototype {
@@ -260,6 +326,7 @@ impl Context {
             extensions: extensions,
             api: api,
             version: version,
+            min_version: min_version,
             config_id: config_id,
             pixel_format: pixel_format,
         })
@@ -273,7 +340,7 @@ impl GlContext for Context {
         if ret == 0 {
             match self.egl.GetError() as u32 {
                 ffi::egl::CONTEXT_LOST => return Err(ContextError::ContextLost),
-                err => panic!("eglMakeCurrent failed (eglGetError returned 0x{:x})", err)
+                err => Err(os_error("eglMakeCurrent", err))
             }
 
         } else {
@@ -303,7 +370,64 @@ impl GlContext for Context {
         if ret == 0 {
             match unsafe { self.egl.GetError() } as u32 {
                 ffi::egl::CONTEXT_LOST => return Err(ContextError::ContextLost),
-                err => panic!("eglSwapBuffers failed (eglGetError returned 0x{:x})", err)
+                err => Err(os_error("eglSwapBuffers", err))
+            }
+
+        } else {
+            self.reset_status.check(self.is_current())
+        }
+    }
+
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        let rects = rects_to_egl(rects);
+
+        let ret = unsafe {
+            if self.has_extension("EGL_KHR_swap_buffers_with_damage") &&
+               self.extra_functions.SwapBuffersWithDamageKHR.is_loaded()
+            {
+                self.extra_functions.SwapBuffersWithDamageKHR(self.display, self.surface,
+                                                              rects.as_ptr() as *mut _,
+                                                              (rects.len() / 4) as i32)
+            } else if self.has_extension("EGL_EXT_swap_buffers_with_damage") &&
+                      self.extra_functions.SwapBuffersWithDamageEXT.is_loaded()
+            {
+                self.extra_functions.SwapBuffersWithDamageEXT(self.display, self.surface,
+                                                              rects.as_ptr() as *mut _,
+                                                              (rects.len() / 4) as i32)
+            } else {
+                return self.swap_buffers();
+            }
+        };
+
+        if ret == 0 {
+            match unsafe { self.egl.GetError() } as u32 {
+                ffi::egl::CONTEXT_LOST => return Err(ContextError::ContextLost),
+                err => Err(os_error("eglSwapBuffersWithDamage", err))
+            }
+
+        } else {
+            self.reset_status.check(self.is_current())
+        }
+    }
+
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        if !self.has_extension("EGL_KHR_partial_update") ||
+           !self.extra_functions.SetDamageRegionKHR.is_loaded()
+        {
+            return Ok(());
+        }
+
+        let rects = rects_to_egl(rects);
+        let ret = unsafe {
+            self.extra_functions.SetDamageRegionKHR(self.display, self.surface,
+                                                    rects.as_ptr() as *mut _,
+                                                    (rects.len() / 4) as i32)
+        };
+
+        if ret == 0 {
+            match unsafe { self.egl.GetError() } as u32 {
+                ffi::egl::CONTEXT_LOST => return Err(ContextError::ContextLost),
+                err => Err(os_error("eglSetDamageRegionKHR", err))
             }
 
         } else {
@@ -311,17 +435,103 @@ impl GlContext for Context {
         }
     }
 
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.reset_status.get(self.is_current())
+    }
+
+    fn buffer_age(&self) -> Option<u32> {
+        if !self.has_extension("EGL_EXT_buffer_age") &&
+           !self.has_extension("EGL_KHR_partial_update")
+        {
+            return None;
+        }
+
+        let mut age = 0;
+        let ret = unsafe {
+            self.egl.QuerySurface(self.display, self.surface,
+                                  ffi::egl_extra::BUFFER_AGE_EXT as i32, &mut age)
+        };
+
+        if ret == 0 {
+            None
+        } else {
+            Some(age as u32)
+        }
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         self.api
     }
 
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.version
+    }
+
     #[inline]
     fn get_pixel_format(&self) -> PixelFormat {
         self.pixel_format.clone()
     }
 }
 
+impl Context {
+    #[inline]
+    fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().find(|s| s == &name).is_some()
+    }
+
+    /// Sets the swap interval of the surface, which EGL clamps to the bounds of the config.
+    ///
+    /// Like when the context is created, it is made current for the duration of the call.
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), ContextError> {
+        unsafe {
+            match with_current(&self.egl, self.display, self.surface, self.context, || {
+                if self.egl.SwapInterval(self.display, interval) == 0 {
+                    Err(os_error("eglSwapInterval", self.egl.GetError() as u32))
+                } else {
+                    Ok(())
+                }
+            }) {
+                Some(result) => result,
+                None => Err(os_error("eglMakeCurrent", self.egl.GetError() as u32)),
+            }
+        }
+    }
+}
+
+/// Returns the `EGL_CONFIG_ID` of `config`.
+unsafe fn query_config_id(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisplay,
+                          config: ffi::egl::types::EGLConfig) -> Option<i32>
+{
+    let mut value = 0;
+    let ret = egl.GetConfigAttrib(display, config, ffi::egl::CONFIG_ID as ffi::egl::types::EGLint,
+                                  &mut value);
+    if ret != 0 { Some(value as i32) } else { None }
+}
+
+/// Builds the error returned when `function` failed and `eglGetError` returned `code`.
+fn os_error(function: &str, code: u32) -> ContextError {
+    ContextError::BackendError {
+        backend: Backend::Egl,
+        code: Some(code as i32),
+        message: format!("{} failed (eglGetError returned 0x{:x})", function, code),
+    }
+}
+
+/// Turns a list of rectangles into the flat `x, y, width, height` list expected by EGL.
+fn rects_to_egl(rects: &[Rect]) -> Vec<ffi::egl::types::EGLint> {
+    let mut out = Vec::with_capacity(rects.len() * 4);
+    for rect in rects {
+        out.push(rect.x as ffi::egl::types::EGLint);
+        out.push(rect.y as ffi::egl::types::EGLint);
+        out.push(rect.width as ffi::egl::types::EGLint);
+        out.push(rect.height as ffi::egl::types::EGLint);
+    }
+    out
+}
+
 unsafe impl Send for Context {}
 unsafe impl Sync for Context {}
 
@@ -332,7 +542,17 @@ impl Drop for Context {
             // is still the current one
             self.egl.DestroyContext(self.display, self.context);
             self.egl.DestroySurface(self.display, self.surface);
-            self.egl.Terminate(self.display);
+
+            let mut users = DISPLAY_USERS.lock().unwrap();
+            let remaining = {
+                let count = users.entry(self.display as usize).or_insert(1);
+                *count -= 1;
+                *count
+            };
+            if remaining == 0 {
+                users.remove(&(self.display as usize));
+                self.egl.Terminate(self.display);
+            }
         }
     }
 }
@@ -345,18 +565,26 @@ pub struct ContextPrototype<'a> {
     extensions: Vec<String>,
     api: Api,
     version: Option<(u8, u8)>,
+    min_version: Option<(u8, u8)>,
     config_id: ffi::egl::types::EGLConfig,
     pixel_format: PixelFormat,
 }
 
 impl<'a> ContextPrototype<'a> {
-    pub fn get_native_visual_id(&self) -> ffi::egl::types::EGLint {
+    pub fn get_native_visual_id(&self) -> Result<ffi::egl::types::EGLint, CreationError> {
         let mut value = unsafe { mem::uninitialized() };
         let ret = unsafe { self.egl.GetConfigAttrib(self.display, self.config_id,
                                                     ffi::egl::NATIVE_VISUAL_ID
                                                     as ffi::egl::types::EGLint, &mut value) };
-        if ret == 0 { panic!("eglGetConfigAttrib failed") };
-        value
+        if ret == 0 {
+            let code = unsafe { self.egl.GetError() };
+            return Err(CreationError::BackendError {
+                backend: Backend::Egl,
+                code: Some(code as i32),
+                message: format!("eglGetConfigAttrib failed (eglGetError returned 0x{:x})", code),
+            });
+        }
+        Ok(value)
     }
 
     pub fn finish(self, native_window: ffi::EGLNativeWindowType)
@@ -396,66 +624,116 @@ impl<'a> ContextPrototype<'a> {
     fn finish_impl(self, surface: ffi::egl::types::EGLSurface)
                    -> Result<Context, CreationError>
     {
-        let context = unsafe {
-            if let Some(version) = self.version {
-                try!(create_context(&self.egl, self.display, &self.egl_version,
-                                    &self.extensions, self.api, version, self.config_id,
-                                    self.opengl.debug, self.opengl.robustness))
-
-            } else if self.api == Api::OpenGlEs {
-                if let Ok(ctxt) = create_context(&self.egl, self.display, &self.egl_version,
-                                                 &self.extensions, self.api, (2, 0), self.config_id,
-                                                 self.opengl.debug, self.opengl.robustness)
-                {
-                    ctxt
-                } else if let Ok(ctxt) = create_context(&self.egl, self.display, &self.egl_version,
-                                                        &self.extensions, self.api, (1, 0),
-                                                        self.config_id, self.opengl.debug,
-                                                        self.opengl.robustness)
-                {
-                    ctxt
-                } else {
-                    return Err(CreationError::OpenGlVersionNotSupported);
-                }
+        // without `EGL_KHR_create_context`, the version of desktop OpenGL contexts can't be
+        // chosen and only the major version of OpenGL ES contexts can
+        let has_create_context = self.egl_version >= (1, 5) ||
+                                 self.extensions.iter()
+                                                .find(|s| s == &"EGL_KHR_create_context")
+                                                .is_some();
+
+        let create = |version| unsafe {
+            create_context(&self.egl, self.display, &self.egl_version, &self.extensions,
+                           self.api, version, self.config_id, self.opengl.profile,
+                           self.opengl.forward_compatible, self.opengl.debug,
+                           self.opengl.robustness)
+        };
+
+        let config_id = unsafe { query_config_id(&self.egl, self.display, self.config_id) };
+        let attempt = |version, err| {
+            CreationAttempt {
+                backend: Backend::Egl,
+                library: None,
+                version: Some((self.api, version)),
+                profile: self.opengl.profile,
+                config_id: config_id,
+                error: err,
+            }
+        };
+
+        // every failed attempt is recorded, so that the error tells what was tried
+        let mut attempts = Vec::new();
+        let min_version = self.min_version.unwrap_or((1, 0));
 
+        let versions = if let Some(version) = self.version {
+            vec![version]
+
+        } else if !has_create_context && self.api == Api::OpenGl {
+            // the version of the context is unknown, so it can't satisfy a minimum
+            if min_version > (1, 0) {
+                vec![]
             } else {
-                if let Ok(ctxt) = create_context(&self.egl, self.display, &self.egl_version,
-                                                 &self.extensions, self.api, (3, 2), self.config_id,
-                                                 self.opengl.debug, self.opengl.robustness)
-                {
-                    ctxt
-                } else if let Ok(ctxt) = create_context(&self.egl, self.display, &self.egl_version,
-                                                        &self.extensions, self.api, (3, 1),
-                                                        self.config_id, self.opengl.debug,
-                                                        self.opengl.robustness)
-                {
-                    ctxt
-                } else if let Ok(ctxt) = create_context(&self.egl, self.display, &self.egl_version,
-                                                        &self.extensions, self.api, (1, 0),
-                                                        self.config_id, self.opengl.debug,
-                                                        self.opengl.robustness)
-                {
-                    ctxt
-                } else {
-                    return Err(CreationError::OpenGlVersionNotSupported);
-                }
+                vec![(1, 0)]
+            }
+
+        } else {
+            versions::latest(self.api, min_version, self.opengl.profile,
+                             self.opengl.forward_compatible)
+        };
+
+        if versions.is_empty() {
+            attempts.push(attempt(min_version, CreationError::OpenGlVersionNotSupported));
+        }
+
+        let mut result = None;
+        for version in versions {
+            match create(version) {
+                Ok(ctxt) => {
+                    result = Some((ctxt, if has_create_context { Some(version) } else { None }));
+                    break;
+                },
+                Err(err) => attempts.push(attempt(version, err)),
             }
+        }
+
+        let (context, version) = match result {
+            Some(result) => result,
+            None => return Err(CreationError::AttemptsFailed(attempts)),
         };
 
+        // vsync
+        if let Err(err) = unsafe { set_swap_interval(&self.egl, self.display, self.config_id,
+                                                     surface, context, self.opengl.vsync,
+                                                     self.opengl.strict) }
+        {
+            unsafe {
+                self.egl.DestroyContext(self.display, context);
+                self.egl.DestroySurface(self.display, surface);
+            }
+            return Err(err);
+        }
+
+        // loading the extra EGL functions
+        let extra_functions = ffi::egl_extra::Egl::load_with(|sym| {
+            let sym = CString::new(sym).unwrap();
+            unsafe { self.egl.GetProcAddress(sym.as_ptr()) as *const _ }
+        });
+
+        let reset_status = ResetStatusQuery::new(self.opengl.robustness, |addr| {
+            let addr = CString::new(addr).unwrap();
+            unsafe { self.egl.GetProcAddress(addr.as_ptr()) as *const () }
+        });
+
+        *DISPLAY_USERS.lock().unwrap().entry(self.display as usize).or_insert(0) += 1;
+
         Ok(Context {
             egl: self.egl,
+            extra_functions: extra_functions,
+            extensions: self.extensions,
             display: self.display,
             context: context,
             surface: surface,
             api: self.api,
+            version: version,
             pixel_format: self.pixel_format,
+            reset_status: reset_status,
         })
     }
 }
 
 unsafe fn choose_fbconfig(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisplay,
                           egl_version: &(ffi::egl::types::EGLint, ffi::egl::types::EGLint),
-                          api: Api, version: Option<(u8, u8)>, reqs: &PixelFormatRequirements)
+                          api: Api, version: Option<(u8, u8)>, reqs: &PixelFormatRequirements,
+                          visual_filter: &Fn(ffi::egl::types::EGLint) -> bool)
                           -> Result<(ffi::egl::types::EGLConfig, PixelFormat), CreationError>
 {
     let descriptor = {
@@ -494,7 +772,16 @@ unsafe fn choose_fbconfig(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisp
                     out.push(ffi::egl::OPENGL_ES_BIT as c_int);
                 }
             },
-            (Api::OpenGlEs, _) => unimplemented!(),
+            (Api::OpenGlEs, _) => {
+                // the latest version is requested, which is at least OpenGL ES 2.0 on any
+                // implementation that supports EGL 1.3
+                if egl_version >= &(1, 3) {
+                    out.push(ffi::egl::RENDERABLE_TYPE as c_int);
+                    out.push(ffi::egl::OPENGL_ES2_BIT as c_int);
+                    out.push(ffi::egl::CONFORMANT as c_int);
+                    out.push(ffi::egl::OPENGL_ES2_BIT as c_int);
+                }
+            },
             (Api::OpenGl, _) => {
                 if egl_version < &(1, 3) { return Err(CreationError::NoAvailablePixelFormat); }
                 out.push(ffi::egl::RENDERABLE_TYPE as c_int);
@@ -565,15 +852,28 @@ unsafe fn choose_fbconfig(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisp
         out
     };
 
-    // calling `eglChooseConfig`
-    let mut config_id = mem::uninitialized();
-    let mut num_configs = mem::uninitialized();
-    if egl.ChooseConfig(display, descriptor.as_ptr(), &mut config_id, 1, &mut num_configs) == 0 {
+    // calling `eglChooseConfig`, which returns the matching configs sorted by preference
+    let mut num_configs = 0;
+    if egl.ChooseConfig(display, descriptor.as_ptr(), ptr::null_mut(), 0, &mut num_configs) == 0 {
         return Err(CreationError::OsError(format!("eglChooseConfig failed")));
     }
-    if num_configs == 0 {
-        return Err(CreationError::NoAvailablePixelFormat);
+    let mut configs = vec![ptr::null(); num_configs as usize];
+    if egl.ChooseConfig(display, descriptor.as_ptr(), configs.as_mut_ptr(), num_configs,
+                        &mut num_configs) == 0
+    {
+        return Err(CreationError::OsError(format!("eglChooseConfig failed")));
     }
+    configs.truncate(num_configs as usize);
+
+    // the first config whose visual is accepted
+    let config_id = match configs.into_iter().find(|&config| {
+        let mut visual_id = 0;
+        egl.GetConfigAttrib(display, config, ffi::egl::NATIVE_VISUAL_ID as i32,
+                            &mut visual_id) != 0 && visual_filter(visual_id)
+    }) {
+        Some(config_id) => config_id,
+        None => return Err(CreationError::NoAvailablePixelFormat),
+    };
 
     // analyzing each config
     macro_rules! attrib {
@@ -611,10 +911,89 @@ unsafe fn choose_fbconfig(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisp
     Ok((config_id, desc))
 }
 
+/// Applies the swap interval requested by `vsync` to `surface`.
+///
+/// `eglSwapInterval` works on the surface bound to the current context, so the context is made
+/// current for the duration of the call, after which the context that was current on the thread
+/// is restored. EGL defaults to an interval of `1`, which is why the interval is set even when
+/// vsync is disabled.
+unsafe fn set_swap_interval(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisplay,
+                            config_id: ffi::egl::types::EGLConfig,
+                            surface: ffi::egl::types::EGLSurface,
+                            context: ffi::egl::types::EGLContext, vsync: bool, strict: bool)
+                            -> Result<(), CreationError>
+{
+    let requested = if vsync { 1 } else { 0 };
+
+    let (mut min, mut max) = (requested, requested);
+    if egl.GetConfigAttrib(display, config_id, ffi::egl::MIN_SWAP_INTERVAL as i32, &mut min) == 0 ||
+       egl.GetConfigAttrib(display, config_id, ffi::egl::MAX_SWAP_INTERVAL as i32, &mut max) == 0
+    {
+        if strict {
+            return Err(CreationError::OsError(format!("eglGetConfigAttrib failed")));
+        }
+
+        // the bounds are unknown, so we try the requested interval anyway
+        min = requested;
+        max = requested;
+    }
+
+    let interval = if requested < min { min } else if requested > max { max } else { requested };
+    if interval != requested && strict {
+        return Err(CreationError::OsError(format!("Couldn't setup vsync: expected interval `{}` \
+                                                   but the config only supports `{}` to `{}`",
+                                                  requested, min, max)));
+    }
+
+    let (ret, error) = match with_current(egl, display, surface, context, || {
+        let ret = egl.SwapInterval(display, interval);
+        (ret, if ret == 0 { egl.GetError() } else { ffi::egl::SUCCESS as i32 })
+    }) {
+        Some(result) => result,
+        None => return Err(CreationError::OsError(format!("eglMakeCurrent failed"))),
+    };
+
+    if ret == 0 && strict {
+        return Err(CreationError::OsError(format!("eglSwapInterval failed (eglGetError returned \
+                                                   0x{:x})", error)));
+    }
+
+    Ok(())
+}
+
+/// Calls `f` while `context` is current on `surface`, then restores the context that was
+/// current on the thread. Returns `None` if `context` couldn't be made current.
+unsafe fn with_current<T, F>(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisplay,
+                             surface: ffi::egl::types::EGLSurface,
+                             context: ffi::egl::types::EGLContext, f: F) -> Option<T>
+    where F: FnOnce() -> T
+{
+    let previous_display = egl.GetCurrentDisplay();
+    let previous_draw = egl.GetCurrentSurface(ffi::egl::DRAW as i32);
+    let previous_read = egl.GetCurrentSurface(ffi::egl::READ as i32);
+    let previous_context = egl.GetCurrentContext();
+
+    if egl.MakeCurrent(display, surface, surface, context) == 0 {
+        return None;
+    }
+
+    let result = f();
+
+    if previous_display.is_null() {
+        egl.MakeCurrent(display, ffi::egl::NO_SURFACE, ffi::egl::NO_SURFACE,
+                        ffi::egl::NO_CONTEXT);
+    } else {
+        egl.MakeCurrent(previous_display, previous_draw, previous_read, previous_context);
+    }
+
+    Some(result)
+}
+
 unsafe fn create_context(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDisplay,
                          egl_version: &(ffi::egl::types::EGLint, ffi::egl::types::EGLint),
                          extensions: &[String], api: Api, version: (u8, u8),
-                         config_id: ffi::egl::types::EGLConfig, gl_debug: bool,
+                         config_id: ffi::egl::types::EGLConfig, gl_profile: Option<GlProfile>,
+                         gl_forward_compatible: bool, gl_debug: bool,
                          gl_robustness: Robustness)
                          -> Result<ffi::egl::types::EGLContext, CreationError>
 {
@@ -629,6 +1008,29 @@ unsafe fn create_context(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDispl
         context_attributes.push(ffi::egl::CONTEXT_MINOR_VERSION as i32);
         context_attributes.push(version.1 as i32);
 
+        // handling the profile, which only exists since OpenGL 3.2
+        if let Some(profile) = gl_profile {
+            if api == Api::OpenGl && version >= (3, 2) {
+                let flag = match profile {
+                    GlProfile::Compatibility => ffi::egl::CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT,
+                    GlProfile::Core => ffi::egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                };
+
+                context_attributes.push(ffi::egl::CONTEXT_OPENGL_PROFILE_MASK as i32);
+                context_attributes.push(flag as i32);
+            }
+        }
+
+        // requesting a forward-compatible context below 3.0 is an error
+        if gl_forward_compatible && api == Api::OpenGl && version >= (3, 0) {
+            if egl_version >= &(1, 5) {
+                context_attributes.push(ffi::egl::CONTEXT_OPENGL_FORWARD_COMPATIBLE as i32);
+                context_attributes.push(ffi::egl::TRUE as i32);
+            } else {
+                flags = flags | ffi::egl::CONTEXT_OPENGL_FORWARD_COMPATIBLE_BIT_KHR as c_int;
+            }
+        }
+
         // handling robustness
         let supports_robustness = egl_version >= &(1, 5) ||
                                   extensions.iter()
@@ -723,7 +1125,13 @@ unsafe fn create_context(egl: &ffi::egl::Egl, display: ffi::egl::types::EGLDispl
     if context.is_null() {
         match egl.GetError() as u32 {
             ffi::egl::BAD_ATTRIBUTE => return Err(CreationError::OpenGlVersionNotSupported),
-            e => panic!("eglCreateContext failed: 0x{:x}", e),
+            // returned by `EGL_KHR_create_context` if the version isn't supported
+            ffi::egl::BAD_MATCH => return Err(CreationError::OpenGlVersionNotSupported),
+            e => return Err(CreationError::BackendError {
+                backend: Backend::Egl,
+                code: Some(e as i32),
+                message: format!("eglCreateContext failed (eglGetError returned 0x{:x})", e),
+            }),
         }
     }
 
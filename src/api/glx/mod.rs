@@ -7,26 +7,40 @@ use GlContext;
 use GlProfile;
 use GlRequest;
 use Api;
+use Backend;
+use CreationAttempt;
 use PixelFormat;
 use PixelFormatRequirements;
 use ReleaseBehavior;
+use ResetStatus;
 use Robustness;
+use SyncValues;
 
 use libc;
 use libc::c_int;
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
-use std::{mem, ptr};
+use std::sync::Arc;
+use std::{mem, ptr, slice};
 
 use api::x11::ffi;
+use api::x11::XConnection;
+use robustness::ResetStatusQuery;
+use versions;
 
 use platform::Window as PlatformWindow;
 
 pub struct Context {
     glx: ffi::glx::Glx,
+    extra_functions: ffi::glx_extra::Glx,
+    extensions: String,
     display: *mut ffi::Display,
     window: ffi::Window,
     context: ffi::GLXContext,
+    api: Api,
+    version: Option<(u8, u8)>,
     pixel_format: PixelFormat,
+    reset_status: ResetStatusQuery,
 }
 
 // TODO: remove me
@@ -36,15 +50,27 @@ fn with_c_str<F, T>(s: &str, f: F) -> T where F: FnOnce(*const libc::c_char) ->
     f(c_str.as_ptr())
 }
 
+/// Builds the error returned when `function` failed.
+fn os_error(function: &str) -> ContextError {
+    ContextError::BackendError {
+        backend: Backend::Glx,
+        code: None,
+        message: format!("{} failed", function),
+    }
+}
+
 impl Context {
     pub fn new<'a>(
         glx: ffi::glx::Glx,
-        xlib: &ffi::Xlib,
+        xconn: &Arc<XConnection>,
         pf_reqs: &PixelFormatRequirements,
         opengl: &'a GlAttributes<&'a Context>,
-        display: *mut ffi::Display,
         screen_id: libc::c_int,
+        visual_filter: &Fn(ffi::VisualID) -> bool,
 ) -> Result<ContextPrototype<'a>, CreationError> {
+        let xlib = &xconn.xlib;
+        let display = xconn.display;
+
         // This is completely ridiculous, but VirtualBox's OpenGL driver needs some call handled by
         // *it* (i.e. not Mesa) to occur before anything else can happen. That is because
         // VirtualBox's OpenGL driver is going to apply binary patches to Mesa in the DLL
@@ -66,7 +92,8 @@ impl Context {
 
         // finding the pixel format we want
         let (fb_config, pixel_format) = unsafe {
-            try!(choose_fbconfig(&glx, &extensions, xlib, display, screen_id, pf_reqs)
+            try!(choose_fbconfig(&glx, &extensions, xlib, display, screen_id, pf_reqs,
+                                 visual_filter)
                                           .map_err(|_| CreationError::NoAvailablePixelFormat))
         };
 
@@ -85,6 +112,7 @@ impl Context {
             glx: glx,
             extensions: extensions,
             opengl: opengl,
+            xconn: xconn.clone(),
             display: display,
             fb_config: fb_config,
             visual_infos: unsafe { mem::transmute(visual_infos) },
@@ -95,10 +123,11 @@ impl Context {
 
 impl GlContext for Context {
     unsafe fn make_current(&self) -> Result<(), ContextError> {
-        // TODO: glutin needs some internal changes for proper error recovery
+        // the X error that caused the failure is reported asynchronously to the error handler
+        // of the display, so it can't be retrieved here
         let res = self.glx.MakeCurrent(self.display as *mut _, self.window, self.context);
         if res == 0 {
-            panic!("glx::MakeCurrent failed");
+            return Err(os_error("glXMakeCurrent"));
         }
         Ok(())
     }
@@ -120,12 +149,90 @@ impl GlContext for Context {
     fn swap_buffers(&self) -> Result<(), ContextError> {
         // TODO: glutin needs some internal changes for proper error recovery
         unsafe { self.glx.SwapBuffers(self.display as *mut _, self.window); }
-        Ok(())
+        self.reset_status.check(self.is_current())
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.reset_status.get(self.is_current())
+    }
+
+    fn buffer_age(&self) -> Option<u32> {
+        if self.extensions.split(' ').find(|&i| i == "GLX_EXT_buffer_age").is_none() {
+            return None;
+        }
+
+        let mut age = 0;
+        unsafe {
+            self.glx.QueryDrawable(self.display as *mut _, self.window,
+                                   ffi::glx_extra::BACK_BUFFER_AGE_EXT as i32, &mut age);
+        }
+        Some(age as u32)
+    }
+
+    fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        if !self.supports_sync_control() {
+            return Err(ContextError::FunctionUnavailable);
+        }
+
+        let (mut ust, mut msc, mut sbc) = (0, 0, 0);
+        let ret = unsafe {
+            self.extra_functions.GetSyncValuesOML(self.display as *mut _, self.window,
+                                                  &mut ust, &mut msc, &mut sbc)
+        };
+
+        if ret == 0 {
+            return Err(os_error("glXGetSyncValuesOML"));
+        }
+
+        Ok(SyncValues { ust: ust, msc: msc, sbc: sbc })
+    }
+
+    fn swap_buffers_msc(&self, target_msc: i64, divisor: i64, remainder: i64)
+                        -> Result<i64, ContextError>
+    {
+        if !self.supports_sync_control() {
+            return Err(ContextError::FunctionUnavailable);
+        }
+
+        let sbc = unsafe {
+            self.extra_functions.SwapBuffersMscOML(self.display as *mut _, self.window,
+                                                   target_msc, divisor, remainder)
+        };
+
+        if sbc < 0 {
+            return Err(os_error("glXSwapBuffersMscOML"));
+        }
+
+        Ok(sbc)
+    }
+
+    fn wait_for_sbc(&self, target_sbc: i64) -> Result<SyncValues, ContextError> {
+        if !self.supports_sync_control() {
+            return Err(ContextError::FunctionUnavailable);
+        }
+
+        let (mut ust, mut msc, mut sbc) = (0, 0, 0);
+        let ret = unsafe {
+            self.extra_functions.WaitForSbcOML(self.display as *mut _, self.window, target_sbc,
+                                               &mut ust, &mut msc, &mut sbc)
+        };
+
+        if ret == 0 {
+            return Err(os_error("glXWaitForSbcOML"));
+        }
+
+        Ok(SyncValues { ust: ust, msc: msc, sbc: sbc })
     }
 
     #[inline]
     fn get_api(&self) -> ::Api {
-        ::Api::OpenGl
+        self.api
+    }
+
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.version
     }
 
     #[inline]
@@ -134,6 +241,16 @@ impl GlContext for Context {
     }
 }
 
+impl Context {
+    #[inline]
+    fn supports_sync_control(&self) -> bool {
+        self.extensions.split(' ').find(|&i| i == "GLX_OML_sync_control").is_some() &&
+        self.extra_functions.GetSyncValuesOML.is_loaded() &&
+        self.extra_functions.SwapBuffersMscOML.is_loaded() &&
+        self.extra_functions.WaitForSbcOML.is_loaded()
+    }
+}
+
 unsafe impl Send for Context {}
 unsafe impl Sync for Context {}
 
@@ -153,6 +270,7 @@ pub struct ContextPrototype<'a> {
     glx: ffi::glx::Glx,
     extensions: String,
     opengl: &'a GlAttributes<&'a Context>,
+    xconn: Arc<XConnection>,
     display: *mut ffi::Display,
     fb_config: ffi::glx::types::GLXFBConfig,
     visual_infos: ffi::XVisualInfo,
@@ -178,42 +296,95 @@ impl<'a> ContextPrototype<'a> {
             })
         });
 
-        // creating GL context
-        let context = match self.opengl.version {
-            GlRequest::Latest => {
-                if let Ok(ctxt) = create_context(&self.glx, &extra_functions, &self.extensions, (3, 2),
-                                                 self.opengl.profile, self.opengl.debug,
-                                                 self.opengl.robustness, share,
-                                                 self.display, self.fb_config, &self.visual_infos)
-                {
-                    ctxt
-                } else if let Ok(ctxt) = create_context(&self.glx, &extra_functions, &self.extensions,
-                                                        (3, 1), self.opengl.profile,
-                                                        self.opengl.debug,
-                                                        self.opengl.robustness, share, self.display,
-                                                        self.fb_config, &self.visual_infos)
-                {
-                    ctxt
+        // without `GLX_ARB_create_context`, the version of the context can't be chosen
+        let has_create_context = self.extensions.split(' ')
+                                                .find(|&i| i == "GLX_ARB_create_context")
+                                                .is_some();
+
+        let config_id = unsafe { fbconfig_id(&self.glx, self.display, self.fb_config) };
+
+        // every failed attempt is recorded, so that the error tells what was tried
+        let attempts = RefCell::new(Vec::new());
+        let fail = |api, version, err| {
+            attempts.borrow_mut().push(CreationAttempt {
+                backend: Backend::Glx,
+                library: None,
+                version: Some((api, version)),
+                profile: self.opengl.profile,
+                config_id: config_id,
+                error: err,
+            });
+        };
 
-                } else {
-                    try!(create_context(&self.glx, &extra_functions, &self.extensions, (1, 0),
-                                        self.opengl.profile, self.opengl.debug,
-                                        self.opengl.robustness,
-                                        share, self.display, self.fb_config, &self.visual_infos))
+        let create = |api, version| {
+            create_context(&self.glx, &extra_functions, &self.xconn, &self.extensions, api,
+                           version, self.opengl.profile, self.opengl.forward_compatible,
+                           self.opengl.debug, self.opengl.robustness, share, self.fb_config,
+                           &self.visual_infos)
+                .map_err(|err| fail(api, version, err))
+        };
+
+        let create_specific = |api, version| {
+            create(api, version).map(|ctxt| {
+                (ctxt, api, if has_create_context { Some(version) } else { None })
+            })
+        };
+
+        let create_latest = |api, min_version| {
+            if !has_create_context {
+                // the version of a legacy context is unknown, so it can't satisfy a minimum
+                if min_version > (1, 0) {
+                    fail(api, min_version, CreationError::OpenGlVersionNotSupported);
+                    return Err(());
                 }
+                return create(api, (1, 0)).map(|ctxt| (ctxt, api, None));
+            }
+
+            let versions = versions::latest(api, min_version, self.opengl.profile,
+                                            self.opengl.forward_compatible);
+            if versions.is_empty() {
+                fail(api, min_version, CreationError::OpenGlVersionNotSupported);
+            }
+
+            for version in versions {
+                if let Ok(ctxt) = create(api, version) {
+                    return Ok((ctxt, api, Some(version)));
+                }
+            }
+
+            Err(())
+        };
+
+        // creating GL context
+        let result = match self.opengl.version {
+            GlRequest::Latest => create_latest(Api::OpenGl, (1, 0)),
+            GlRequest::Specific(Api::OpenGl, version) => create_specific(Api::OpenGl, version),
+            GlRequest::Specific(Api::OpenGlEs, version) => {
+                create_specific(Api::OpenGlEs, version)
+            },
+            GlRequest::Specific(_, _) => return Err(CreationError::NotSupported),
+            GlRequest::AtLeast(Api::OpenGl, min_version) => {
+                create_latest(Api::OpenGl, min_version)
+            },
+            GlRequest::AtLeast(Api::OpenGlEs, min_version) => {
+                create_latest(Api::OpenGlEs, min_version)
             },
-            GlRequest::Specific(Api::OpenGl, (major, minor)) => {
-                try!(create_context(&self.glx, &extra_functions, &self.extensions, (major, minor),
-                                    self.opengl.profile, self.opengl.debug,
-                                    self.opengl.robustness, share, self.display, self.fb_config,
-                                    &self.visual_infos))
+            GlRequest::AtLeast(_, _) => return Err(CreationError::NotSupported),
+            GlRequest::GlThenGles { opengl_version, opengles_version } => {
+                create_specific(Api::OpenGl, opengl_version)
+                    .or_else(|_| create_specific(Api::OpenGlEs, opengles_version))
             },
-            GlRequest::Specific(_, _) => panic!("Only OpenGL is supported"),
-            GlRequest::GlThenGles { opengl_version: (major, minor), .. } => {
-                try!(create_context(&self.glx, &extra_functions, &self.extensions, (major, minor),
-                                    self.opengl.profile, self.opengl.debug,
-                                    self.opengl.robustness, share, self.display, self.fb_config,
-                                    &self.visual_infos))
+            GlRequest::AtLeastGlThenGles { opengl_version, opengles_version } => {
+                create_latest(Api::OpenGl, opengl_version)
+                    .or_else(|_| create_latest(Api::OpenGlEs, opengles_version))
+            },
+        };
+
+        let (context, api, version) = match result {
+            Ok(result) => result,
+            Err(()) => {
+                let attempts = mem::replace(&mut *attempts.borrow_mut(), Vec::new());
+                return Err(CreationError::AttemptsFailed(attempts));
             },
         };
 
@@ -221,16 +392,15 @@ impl<'a> ContextPrototype<'a> {
         if self.opengl.vsync {
             unsafe { self.glx.MakeCurrent(self.display as *mut _, window, context) };
 
-            if extra_functions.SwapIntervalEXT.is_loaded() {
+            let result = if extra_functions.SwapIntervalEXT.is_loaded() {
                 // this should be the most common extension
                 unsafe {
                     extra_functions.SwapIntervalEXT(self.display as *mut _, window, 1);
                 }
 
                 // checking that it worked
-                // TODO: handle this
-                /*if self.builder.strict {
-                    let mut swap = unsafe { mem::uninitialized() };
+                if self.opengl.strict {
+                    let mut swap = 0;
                     unsafe {
                         self.glx.QueryDrawable(self.display as *mut _, window,
                                                ffi::glx_extra::SWAP_INTERVAL_EXT as i32,
@@ -238,10 +408,14 @@ impl<'a> ContextPrototype<'a> {
                     }
 
                     if swap != 1 {
-                        return Err(CreationError::OsError(format!("Couldn't setup vsync: expected \
-                                                    interval `1` but got `{}`", swap)));
+                        Err(CreationError::OsError(format!("Couldn't setup vsync: expected \
+                                                    interval `1` but got `{}`", swap)))
+                    } else {
+                        Ok(())
                     }
-                }*/
+                } else {
+                    Ok(())
+                }
 
             // GLX_MESA_swap_control is not official
             /*} else if extra_functions.SwapIntervalMESA.is_loaded() {
@@ -253,34 +427,70 @@ impl<'a> ContextPrototype<'a> {
                 unsafe {
                     extra_functions.SwapIntervalSGI(1);
                 }
+                Ok(())
 
-            }/* else if self.builder.strict {
-                // TODO: handle this
-                return Err(CreationError::OsError(format!("Couldn't find any available vsync extension")));
-            }*/
+            } else if self.opengl.strict {
+                Err(CreationError::OsError(format!("Couldn't find any available vsync extension")))
+            } else {
+                Ok(())
+            };
 
             unsafe { self.glx.MakeCurrent(self.display as *mut _, 0, ptr::null()) };
+
+            if let Err(err) = result {
+                unsafe { self.glx.DestroyContext(self.display as *mut _, context) };
+                return Err(err);
+            }
         }
 
+        let reset_status = ResetStatusQuery::new(self.opengl.robustness, |addr| {
+            with_c_str(addr, |s| {
+                unsafe { self.glx.GetProcAddress(s as *const u8) as *const () }
+            })
+        });
+
         Ok(Context {
             glx: self.glx,
+            extra_functions: extra_functions,
+            extensions: self.extensions,
             display: self.display,
             window: window,
             context: context,
+            api: api,
+            version: version,
             pixel_format: self.pixel_format,
+            reset_status: reset_status,
         })
     }
 }
 
-fn create_context(glx: &ffi::glx::Glx, extra_functions: &ffi::glx_extra::Glx, extensions: &str,
-                  version: (u8, u8), profile: Option<GlProfile>, debug: bool,
-                  robustness: Robustness, share: ffi::GLXContext, display: *mut ffi::Display,
+fn create_context(glx: &ffi::glx::Glx, extra_functions: &ffi::glx_extra::Glx,
+                  xconn: &XConnection, extensions: &str,
+                  api: Api, version: (u8, u8), profile: Option<GlProfile>,
+                  forward_compatible: bool, debug: bool,
+                  robustness: Robustness, share: ffi::GLXContext,
                   fb_config: ffi::glx::types::GLXFBConfig,
                   visual_infos: &ffi::XVisualInfo)
                   -> Result<ffi::GLXContext, CreationError>
 {
+    let display = xconn.display;
+    let has_extension = |name: &str| extensions.split(' ').find(|&i| i == name).is_some();
+
+    if api == Api::OpenGlEs {
+        // `GLX_EXT_create_context_es2_profile` only allows OpenGL ES 2.0, while
+        // `GLX_EXT_create_context_es_profile` allows any version
+        let supported = has_extension("GLX_ARB_create_context") &&
+                        (has_extension("GLX_EXT_create_context_es_profile") ||
+                         (version == (2, 0) &&
+                          has_extension("GLX_EXT_create_context_es2_profile")));
+
+        if !supported {
+            return Err(CreationError::OpenGlVersionNotSupported);
+        }
+    }
+
     unsafe {
-        let context = if extensions.split(' ').find(|&i| i == "GLX_ARB_create_context").is_some() {
+        let context = if has_extension("GLX_ARB_create_context") {
             let mut attributes = Vec::with_capacity(9);
 
             attributes.push(ffi::glx_extra::CONTEXT_MAJOR_VERSION_ARB as c_int);
@@ -288,7 +498,10 @@ fn create_context(glx: &ffi::glx::Glx, extra_functions: &ffi::glx_extra::Glx, ex
             attributes.push(ffi::glx_extra::CONTEXT_MINOR_VERSION_ARB as c_int);
             attributes.push(version.1 as c_int);
 
-            if let Some(profile) = profile {
+            if api == Api::OpenGlEs {
+                attributes.push(ffi::glx_extra::CONTEXT_PROFILE_MASK_ARB as c_int);
+                attributes.push(ffi::glx_extra::CONTEXT_ES2_PROFILE_BIT_EXT as c_int);
+            } else if let Some(profile) = profile {
                 let flag = match profile {
                     GlProfile::Compatibility =>
                         ffi::glx_extra::CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
@@ -332,6 +545,11 @@ fn create_context(glx: &ffi::glx::Glx, extra_functions: &ffi::glx_extra::Glx, ex
                     flags = flags | ffi::glx_extra::CONTEXT_DEBUG_BIT_ARB as c_int;
                 }
 
+                // requesting a forward-compatible context below 3.0 is an error
+                if forward_compatible && api == Api::OpenGl && version >= (3, 0) {
+                    flags = flags | ffi::glx_extra::CONTEXT_FORWARD_COMPATIBLE_BIT_ARB as c_int;
+                }
+
                 flags
             };
 
@@ -349,18 +567,41 @@ fn create_context(glx: &ffi::glx::Glx, extra_functions: &ffi::glx_extra::Glx, ex
         };
 
         if context.is_null() {
-            // TODO: check for errors and return `OpenGlVersionNotSupported`
-            return Err(CreationError::OsError(format!("GL context creation failed")));
+            // the X error that caused the failure is delivered asynchronously
+            (xconn.xlib.XSync)(display, 0);
+
+            return Err(match xconn.check_errors() {
+                Err(err) => CreationError::BackendError {
+                    backend: Backend::Glx,
+                    code: Some(err.error_code as i32),
+                    message: format!("GL context creation failed: {}", err),
+                },
+                Ok(()) => CreationError::OsError(format!("GL context creation failed")),
+            });
         }
 
         Ok(context)
     }
 }
 
-/// Enumerates all available FBConfigs
+/// Returns the `GLX_FBCONFIG_ID` of `fb_config`.
+unsafe fn fbconfig_id(glx: &ffi::glx::Glx, display: *mut ffi::Display,
+                      fb_config: ffi::glx::types::GLXFBConfig) -> Option<i32>
+{
+    let mut value = 0;
+    let ret = glx.GetFBConfigAttrib(display as *mut _, fb_config,
+                                    ffi::glx::FBCONFIG_ID as c_int, &mut value);
+
+    // returns `Success` on success
+    if ret == 0 { Some(value) } else { None }
+}
+
+/// Enumerates all available FBConfigs, and returns the first one whose visual is accepted by
+/// `visual_filter`.
 unsafe fn choose_fbconfig(glx: &ffi::glx::Glx, extensions: &str, xlib: &ffi::Xlib,
                           display: *mut ffi::Display, screen_id: libc::c_int,
-                          reqs: &PixelFormatRequirements)
+                          reqs: &PixelFormatRequirements,
+                          visual_filter: &Fn(ffi::VisualID) -> bool)
                           -> Result<(ffi::glx::types::GLXFBConfig, PixelFormat), ()>
 {
     let descriptor = {
@@ -457,16 +698,24 @@ unsafe fn choose_fbconfig(glx: &ffi::glx::Glx, extensions: &str, xlib: &ffi::Xli
         out
     };
 
-    // calling glXChooseFBConfig
+    // calling glXChooseFBConfig, which returns the matching configs sorted by preference
     let fb_config = {
         let mut num_configs = 1;
         let result = glx.ChooseFBConfig(display as *mut _, screen_id, descriptor.as_ptr(),
                                         &mut num_configs);
         if result.is_null() { return Err(()); }
-        if num_configs == 0 { return Err(()); }
-        let val = *result;
+        let val = slice::from_raw_parts(result, num_configs as usize).iter().cloned()
+            .find(|&config| {
+                let mut visual_id = 0;
+                glx.GetFBConfigAttrib(display as *mut _, config, ffi::glx::VISUAL_ID as c_int,
+                                      &mut visual_id) == 0 &&
+                    visual_filter(visual_id as ffi::VisualID)
+            });
         (xlib.XFree)(result as *mut _);
-        val
+        match val {
+            Some(val) => val,
+            None => return Err(()),
+        }
     };
 
     let get_attrib = |attrib: c_int| -> i32 {
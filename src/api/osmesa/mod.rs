@@ -3,6 +3,7 @@
 extern crate osmesa_sys;
 
 use Api;
+use Backend;
 use ContextError;
 use CreationError;
 use GlAttributes;
@@ -14,13 +15,46 @@ use PixelFormatRequirements;
 use Robustness;
 use libc;
 use std::{mem, ptr};
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard};
 use std::ffi::CString;
 
 pub struct OsMesaContext {
     context: osmesa_sys::OSMesaContext,
-    buffer: Vec<u32>,
+    // reallocated when the context is resized
+    buffer: Mutex<Buffer>,
+}
+
+struct Buffer {
+    data: Vec<u32>,
     width: u32,
     height: u32,
+    // the allocation that the context is still bound to after a resize, which can only be freed
+    // once the context is bound to `data` by `make_current`
+    bound: Option<Vec<u32>>,
+}
+
+impl Buffer {
+    fn new(dimensions: (u32, u32)) -> Buffer {
+        Buffer {
+            data: vec![0; (dimensions.0 * dimensions.1) as usize],
+            width: dimensions.0,
+            height: dimensions.1,
+            bound: None,
+        }
+    }
+}
+
+/// The content of the buffer of an `OsMesaContext`, see `get_framebuffer`.
+pub struct Framebuffer<'a>(MutexGuard<'a, Buffer>);
+
+impl<'a> Deref for Framebuffer<'a> {
+    type Target = [u32];
+
+    #[inline]
+    fn deref(&self) -> &[u32] {
+        &self.0.data
+    }
 }
 
 pub enum OsMesaCreationError {
@@ -71,17 +105,22 @@ impl OsMesaContext {
 
         match opengl.version {
             GlRequest::Latest => {},
-            GlRequest::Specific(Api::OpenGl, (major, minor)) => {
+            // OSMesa returns the newest version that is compatible with the requested one
+            GlRequest::Specific(Api::OpenGl, (major, minor)) |
+            GlRequest::AtLeast(Api::OpenGl, (major, minor)) => {
                 attribs.push(osmesa_sys::OSMESA_CONTEXT_MAJOR_VERSION);
                 attribs.push(major as libc::c_int);
                 attribs.push(osmesa_sys::OSMESA_CONTEXT_MINOR_VERSION);
                 attribs.push(minor as libc::c_int);
             },
-            GlRequest::Specific(Api::OpenGlEs, _) => {
+            GlRequest::Specific(Api::OpenGlEs, _) | GlRequest::AtLeast(Api::OpenGlEs, _) => {
+                return Err(OsMesaCreationError::NotSupported);
+            },
+            GlRequest::Specific(_, _) | GlRequest::AtLeast(_, _) => {
                 return Err(OsMesaCreationError::NotSupported);
             },
-            GlRequest::Specific(_, _) => return Err(OsMesaCreationError::NotSupported),
-            GlRequest::GlThenGles { opengl_version: (major, minor), .. } => {
+            GlRequest::GlThenGles { opengl_version: (major, minor), .. } |
+            GlRequest::AtLeastGlThenGles { opengl_version: (major, minor), .. } => {
                 attribs.push(osmesa_sys::OSMESA_CONTEXT_MAJOR_VERSION);
                 attribs.push(major as libc::c_int);
                 attribs.push(osmesa_sys::OSMESA_CONTEXT_MINOR_VERSION);
@@ -93,10 +132,7 @@ impl OsMesaContext {
         attribs.push(0);
 
         Ok(OsMesaContext {
-            width: dimensions.0,
-            height: dimensions.1,
-            buffer: ::std::iter::repeat(unsafe { mem::uninitialized() })
-                .take((dimensions.0 * dimensions.1) as usize).collect(),
+            buffer: Mutex::new(Buffer::new(dimensions)),
             context: unsafe {
                 let ctxt = osmesa_sys::OSMesaCreateContextAttribs(attribs.as_ptr(), ptr::null_mut());
                 if ctxt.is_null() {
@@ -107,14 +143,18 @@ impl OsMesaContext {
         })
     }
 
+    /// Returns the content of the buffer that the context renders to.
+    ///
+    /// The buffer can't be resized while the returned value is alive.
     #[inline]
-    pub fn get_framebuffer(&self) -> &[u32] {
-        &self.buffer
+    pub fn get_framebuffer(&self) -> Framebuffer {
+        Framebuffer(self.buffer.lock().unwrap())
     }
 
     #[inline]
     pub fn get_dimensions(&self) -> (u32, u32) {
-        (self.width, self.height)
+        let buffer = self.buffer.lock().unwrap();
+        (buffer.width, buffer.height)
     }
 
     #[allow(dead_code)]
@@ -127,16 +167,22 @@ impl OsMesaContext {
 impl GlContext for OsMesaContext {
     #[inline]
     unsafe fn make_current(&self) -> Result<(), ContextError> {
-        let ret = osmesa_sys::OSMesaMakeCurrent(self.context, self.buffer.as_ptr()
-                                                as *mut _, 0x1401, self.width
-                                                as libc::c_int, self.height as libc::c_int);
+        let mut buffer = self.buffer.lock().unwrap();
+        let ret = osmesa_sys::OSMesaMakeCurrent(self.context, buffer.data.as_ptr()
+                                                as *mut _, 0x1401, buffer.width
+                                                as libc::c_int, buffer.height as libc::c_int);
 
         // an error can only happen in case of invalid parameter, which would indicate a bug
         // in glutin
         if ret == 0 {
-            panic!("OSMesaMakeCurrent failed");
+            return Err(ContextError::BackendError {
+                backend: Backend::OsMesa,
+                code: None,
+                message: format!("OSMesaMakeCurrent failed"),
+            });
         }
 
+        buffer.bound = None;
         Ok(())
     }
 
@@ -157,6 +203,22 @@ impl GlContext for OsMesaContext {
         Ok(())
     }
 
+    fn resize_surface(&self, width: u32, height: u32) {
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            let old = mem::replace(&mut *buffer, Buffer::new((width, height)));
+
+            // the context may be current on another thread, and keeps rendering to the old
+            // allocation until it is made current again
+            buffer.bound = Some(old.bound.unwrap_or(old.data));
+        }
+
+        // the new buffer is bound right away if we can, so that the old one is freed
+        if self.is_current() {
+            let _ = unsafe { self.make_current() };
+        }
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         Api::OpenGl
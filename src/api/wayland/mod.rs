@@ -1,53 +1,460 @@
 #![cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
 
-use std::ffi::CString;
+mod viewporter;
+
+use std::cmp;
+use std::env;
+use std::i32;
+use std::io;
+use std::mem;
+use std::ops::Deref;
+use std::os::raw::c_void;
+use std::sync::{Arc, Mutex, MutexGuard};
 use winit;
 use winit::os::unix::WindowExt;
-use {ContextError, CreationError, GlAttributes, GlContext, PixelFormat, PixelFormatRequirements};
-use api::dlopen;
+use {Backend, ContextError, CreationError, GlAttributes, GlContext, PixelFormat};
+use PixelFormatRequirements;
+use Rect;
+use WindowAttributes;
+use ResetStatus;
+use api::dlopen::{self, Library, EGL_LIBRARIES, EGL_LIBRARY_VAR};
 use api::egl;
 use api::egl::Context as EglContext;
+use platform::PlatformSpecificWindowBuilderAttributes;
+use window::{buffer_scale, build_winit_window};
 use wayland_client::egl as wegl;
+use wayland_client::{Event as WaylandEvent, EventIterator, Proxy};
+use wayland_client::wayland::{WaylandProtocolEvent, WlCallback, WlCallbackEvent, WlDisplay};
+use wayland_client::wayland::compositor::{WlCompositor, WlSurface};
+use wayland_client::wayland::subcompositor::{WlSubcompositor, WlSubsurface};
+use wayland_sys::client::{wl_display, WAYLAND_CLIENT_HANDLE};
+use libc;
+use self::viewporter::{WpViewport, WpViewporter};
+
+// the globals that winit doesn't give us access to
+wayland_env!(GlobalsEnv,
+    compositor: WlCompositor,
+    subcompositor: WlSubcompositor
+);
+
+/// The globals that we bind on the display of the window.
+struct Globals {
+    env: GlobalsEnv,
+    // `None` if the compositor doesn't support `wp_viewporter`
+    viewporter: Option<WpViewporter>,
+}
 
 pub struct Window {
+    egl_surface: EglWindow,
+    context: EglContext,
+    // must outlive the context, which uses functions loaded from it
+    library: Arc<Library>,
+    display: egl::ffi::EGLNativeDisplayType,
+    frame: Mutex<FrameCallback>,
+    scaling: Mutex<Scaling>,
+    // bound the first time they are needed
+    globals: Mutex<Option<Globals>>,
+}
+
+/// The EGL window of a surface, which also destroys the surface unless it belongs to someone
+/// else.
+struct EglWindow {
+    // only `None` while it is dropped
+    inner: Option<wegl::WlEglSurface>,
+    owned: bool,
+}
+
+impl Deref for EglWindow {
+    type Target = wegl::WlEglSurface;
+
+    #[inline]
+    fn deref(&self) -> &wegl::WlEglSurface {
+        self.inner.as_ref().unwrap()
+    }
+}
+
+impl Drop for EglWindow {
+    fn drop(&mut self) {
+        if !self.owned {
+            // dropping the `WlSurface` would destroy it
+            mem::forget(self.inner.take().unwrap().destroy());
+        }
+    }
+}
+
+/// How the buffers of the surface are sized and scaled to the window.
+struct Scaling {
+    // the size of the window in pixels
+    size: (u32, u32),
+    // the buffer scale of the window
+    scale: u32,
+    // the size of the buffers in pixels when it was decoupled from the size of the window
+    buffer_size: Option<(u32, u32)>,
+    // the viewport that scales the buffers to the window while `buffer_size` is set
+    viewport: Option<WpViewport>,
+}
+
+/// An EGL context rendering to a `wl_subsurface` of a `Window`.
+pub struct Subsurface {
+    // must be destroyed before its surface
+    subsurface: WlSubsurface,
     egl_surface: wegl::WlEglSurface,
     context: EglContext,
+    library: Arc<Library>,
+}
+
+/// The state of the `wl_surface.frame` callbacks of the surface.
+struct FrameCallback {
+    // the queue on which the `done` events of the callbacks are delivered, separate from the one
+    // of winit so that we don't steal its events
+    events: EventIterator,
+    // the callback that was requested and hasn't fired yet
+    pending: Option<WlCallback>,
+    // the timestamp of the last `done` event that wasn't returned by `poll_frame` yet
+    done: Option<u32>,
+    // whether the swap interval was set to 0 because frames are paced by the callbacks
+    paced: bool,
 }
 
 impl Window {
     pub fn new(
+        window: &WindowAttributes,
         pf_reqs: &PixelFormatRequirements,
         opengl: &GlAttributes<&Window>,
-        winit_window: &winit::Window,
-    ) -> Result<Window, CreationError> {
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+        winit_window: Option<winit::Window>,
+    ) -> Result<(Window, winit::Window), CreationError> {
+        let winit_window = match winit_window {
+            Some(w) => w,
+            None => try!(build_winit_window(window, winit::WindowBuilder::new())),
+        };
         let wayland_context = winit_window.get_wayland_context().unwrap();
         let (surface, _) = match wayland_context.new_surface() {
             Some(t) => t,
             None => return Err(CreationError::NotSupported)
         };
+        // the EGL window is sized in pixels, and the compositor is told how many of them there
+        // are per point so that it doesn't upscale the buffer on HiDPI outputs
+        let scale = buffer_scale(winit_window.hidpi_factor());
         let (w, h) = winit_window.get_inner_size().unwrap();
-        let egl_surface = wegl::WlEglSurface::new(surface, w as i32, h as i32);
-        let context = {
-            let libegl = unsafe { dlopen::dlopen(b"libEGL.so\0".as_ptr() as *const _, dlopen::RTLD_NOW) };
-            if libegl.is_null() {
-                return Err(CreationError::NotSupported);
-            }
-            let egl = ::api::egl::ffi::egl::Egl::load_with(|sym| {
-                let sym = CString::new(sym).unwrap();
-                unsafe { dlopen::dlsym(libegl, sym.as_ptr()) }
-            });
-            try!(EglContext::new(
-                egl,
-                pf_reqs, &opengl.clone().map_sharing(|_| unimplemented!()),        // TODO: 
-                egl::NativeDisplay::Wayland(Some(winit_window.get_wayland_display().unwrap())))
-                .and_then(|p| p.finish(unsafe { egl_surface.egl_surfaceptr() } as *const _))
-            )
+        let display = winit_window.get_wayland_display().unwrap() as *const _;
+        let transparent = window.transparent;
+
+        let window = try!(Window::create(surface, true, display, (w * scale, h * scale), scale,
+                                         transparent, pf_reqs, opengl, pl_attribs));
+
+        // buffers with an alpha channel are blended by the compositor unless it is told that the
+        // surface is opaque
+        if !transparent {
+            window.set_opaque();
+        }
+
+        Ok((window, winit_window))
+    }
+
+    /// Creates a context for `surface`, a surface of `display` that wasn't created by winit,
+    /// whose buffers are `dimensions` pixels big.
+    ///
+    /// The role, the input and the opaque region of the surface are left to its owner.
+    pub unsafe fn new_raw(
+        display: *mut c_void,
+        surface: *mut c_void,
+        dimensions: (u32, u32),
+        pf_reqs: &PixelFormatRequirements,
+        opengl: &GlAttributes<&Window>,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+    ) -> Result<Window, CreationError> {
+        // the surface stays owned by the caller, who destroys it
+        let surface = WlSurface::from_ptr_no_own(surface as *mut _);
+        Window::create(surface, false, display as *const _, dimensions, 1, false, pf_reqs, opengl,
+                       pl_attribs)
+    }
+
+    /// Creates the EGL window and the context for `surface`, which is destroyed along with the
+    /// window if `owned` is true.
+    fn create(
+        surface: WlSurface,
+        owned: bool,
+        display: egl::ffi::EGLNativeDisplayType,
+        dimensions: (u32, u32),
+        scale: u32,
+        transparent: bool,
+        pf_reqs: &PixelFormatRequirements,
+        opengl: &GlAttributes<&Window>,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+    ) -> Result<Window, CreationError> {
+        let egl_surface = EglWindow {
+            inner: Some(wegl::WlEglSurface::new(surface, dimensions.0 as i32,
+                                                dimensions.1 as i32)),
+            owned: owned,
+        };
+        egl_surface.set_buffer_scale(scale as i32);
+        let library = match Library::open_first(&dlopen::candidates(EGL_LIBRARY_VAR,
+                                                                    &pl_attribs.egl_libraries,
+                                                                    EGL_LIBRARIES)) {
+            Some(library) => library,
+            None => return Err(no_library_error()),
         };
+
+        // the compositor uses the alpha channel of the buffers, so transparent windows need a
+        // config that has one
+        let mut alpha_reqs = pf_reqs.clone();
+        let needs_alpha = transparent && pf_reqs.alpha_bits.unwrap_or(0) == 0;
+        if needs_alpha {
+            alpha_reqs.alpha_bits = Some(8);
+        }
+
+        let context = match create_context(&library, &alpha_reqs, opengl, display,
+                                           &egl_surface) {
+            Err(ref err) if needs_alpha && !opengl.strict && is_missing_config(err) => {
+                warn!("No config has an alpha channel, the window will be opaque");
+                try!(create_context(&library, pf_reqs, opengl, display, &egl_surface))
+            },
+            Err(ref err) if needs_alpha && is_missing_config(err) => {
+                return Err(CreationError::BackendError {
+                    backend: Backend::Egl,
+                    code: None,
+                    message: format!("No config has an alpha channel, which is required for \
+                                      transparency"),
+                });
+            },
+            result => try!(result),
+        };
+
         Ok(Window {
             egl_surface: egl_surface,
-            context: context
+            context: context,
+            library: library,
+            display: display,
+            frame: Mutex::new(FrameCallback {
+                events: EventIterator::new(),
+                pending: None,
+                done: None,
+                paced: false,
+            }),
+            scaling: Mutex::new(Scaling {
+                size: dimensions,
+                scale: scale,
+                buffer_size: None,
+                viewport: None,
+            }),
+            globals: Mutex::new(None),
         })
     }
+
+    /// Returns the path of the EGL library that was loaded to create the context.
+    #[inline]
+    pub fn get_library_path(&self) -> &str {
+        self.library.path()
+    }
+
+    /// Creates a subsurface of `dimensions` pixels attached to the surface of the window, with a
+    /// context of its own.
+    ///
+    /// `winit_window` must be the window that was returned along with this one.
+    pub fn create_subsurface(&self, winit_window: &winit::Window, dimensions: (u32, u32),
+                             pf_reqs: &PixelFormatRequirements, opengl: &GlAttributes<&Window>)
+                             -> Result<Subsurface, CreationError>
+    {
+        let globals = self.get_globals();
+        let subcompositor = match globals.as_ref().unwrap().env.subcompositor {
+            Some((ref subcompositor, _)) => subcompositor,
+            None => return Err(CreationError::NotSupported),
+        };
+
+        let (surface, _) = match winit_window.get_wayland_context().unwrap().new_surface() {
+            Some(t) => t,
+            None => return Err(CreationError::NotSupported)
+        };
+        let subsurface = subcompositor.get_subsurface(&surface, &self.egl_surface);
+        surface.set_buffer_scale(buffer_scale(winit_window.hidpi_factor()) as i32);
+        let egl_surface = wegl::WlEglSurface::new(surface, dimensions.0 as i32,
+                                                  dimensions.1 as i32);
+
+        let context = try!(create_context(&self.library, pf_reqs, opengl, self.display,
+                                          &egl_surface));
+
+        Ok(Subsurface {
+            subsurface: subsurface,
+            egl_surface: egl_surface,
+            context: context,
+            library: self.library.clone(),
+        })
+    }
+
+    /// Returns the surface of the window.
+    #[inline]
+    pub fn get_surface(&self) -> &WlSurface {
+        &self.egl_surface
+    }
+
+    /// Makes the buffers of the surface `size` pixels big whatever the size of the window, and
+    /// has the compositor scale them to the window through `wp_viewporter`. `None` makes them
+    /// follow the size of the window again.
+    ///
+    /// Returns `false` if the compositor doesn't support `wp_viewporter`.
+    pub fn set_buffer_size(&self, size: Option<(u32, u32)>) -> bool {
+        let mut scaling = self.scaling.lock().unwrap();
+
+        if size.is_some() && scaling.viewport.is_none() {
+            let globals = self.get_globals();
+            scaling.viewport = match globals.as_ref().unwrap().viewporter {
+                Some(ref viewporter) => Some(viewporter.get_viewport(&self.egl_surface)),
+                None => return false,
+            };
+        }
+
+        if size.is_none() {
+            // dropping the viewport destroys it
+            scaling.viewport = None;
+        }
+
+        scaling.buffer_size = size;
+        self.update_scaling(&scaling);
+        true
+    }
+
+    /// Marks the whole surface as opaque, so that the compositor doesn't blend it whatever the
+    /// alpha channel of its buffers contains.
+    fn set_opaque(&self) {
+        let globals = self.get_globals();
+        if let Some((ref compositor, _)) = globals.as_ref().unwrap().env.compositor {
+            // the compositor clips the region to the surface, so it doesn't need to be updated
+            // when the window is resized
+            let region = compositor.create_region();
+            region.add(0, 0, i32::MAX, i32::MAX);
+            self.egl_surface.set_opaque_region(Some(&region));
+            region.destroy();
+        }
+    }
+
+    /// Sizes and scales the buffers of the surface according to `scaling`.
+    fn update_scaling(&self, scaling: &Scaling) {
+        match (scaling.buffer_size, scaling.viewport.as_ref()) {
+            (Some((width, height)), Some(viewport)) => {
+                // the buffers are scaled by the viewport instead
+                self.egl_surface.set_buffer_scale(1);
+                self.egl_surface.resize(width as i32, height as i32, 0, 0);
+                viewport.set_source(0.0, 0.0, width as f64, height as f64);
+                viewport.set_destination((scaling.size.0 / scaling.scale) as i32,
+                                         (scaling.size.1 / scaling.scale) as i32);
+            },
+            _ => {
+                self.egl_surface.set_buffer_scale(scaling.scale as i32);
+                self.egl_surface.resize(scaling.size.0 as i32, scaling.size.1 as i32, 0, 0);
+            },
+        }
+    }
+
+    /// Binds the globals that we need on the display, if that wasn't done yet.
+    fn get_globals(&self) -> MutexGuard<Option<Globals>> {
+        let mut globals = self.globals.lock().unwrap();
+        if globals.is_none() {
+            // we only need the globals, so the events of the registry are dispatched on a queue
+            // of our own, which is dropped once they are listed
+            let display = unsafe { WlDisplay::from_ptr_no_own(self.display as *mut _) };
+            let (env, _) = GlobalsEnv::init(display);
+            let viewporter = WpViewporter::bind(&env.registry, &env.globals);
+            *globals = Some(Globals { env: env, viewporter: viewporter });
+        }
+        globals
+    }
+
+    /// Sets the factor between the size of the buffers in pixels and the size of the surface.
+    /// A scale of 0 is treated as 1, which the protocol requires at least.
+    ///
+    /// Takes effect along with the next buffer, which `Window` resizes right after this.
+    pub fn set_buffer_scale(&self, scale: u32) {
+        let mut scaling = self.scaling.lock().unwrap();
+        scaling.scale = cmp::max(scale, 1);
+        self.update_scaling(&scaling);
+    }
+
+    /// Asks the compositor to notify us when it is a good time to draw a new frame.
+    ///
+    /// The request is sent along with the next buffer, so this must be called before
+    /// `swap_buffers`. Does nothing if a frame was already requested and hasn't been received.
+    pub fn request_frame(&self) {
+        let mut frame = self.frame.lock().unwrap();
+
+        // with a swap interval of 1, `eglSwapBuffers` waits for a frame callback of its own and
+        // blocks for as long as the surface is hidden
+        if !frame.paced {
+            if let Err(err) = self.context.set_swap_interval(0) {
+                warn!("Couldn't disable vsync for the frame callbacks: {}", err);
+            }
+            frame.paced = true;
+        }
+
+        if frame.pending.is_none() {
+            let mut callback = self.egl_surface.frame();
+            callback.set_evt_iterator(&frame.events);
+            frame.pending = Some(callback);
+        }
+    }
+
+    /// Returns the timestamp in milliseconds of the frame that was requested with
+    /// `request_frame` if the compositor has signaled it, or `None` otherwise.
+    ///
+    /// Each signaled frame is only returned once.
+    pub fn poll_frame(&self) -> Option<u32> {
+        // the events have already been read from the socket by winit's event loop
+        let ret = unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_dispatch_pending,
+                          self.display as *mut wl_display)
+        };
+        if ret < 0 {
+            warn!("Couldn't dispatch the Wayland events: {}", io::Error::last_os_error());
+        }
+
+        let mut frame = self.frame.lock().unwrap();
+        frame.receive();
+        frame.done.take()
+    }
+
+    /// Blocks until the frame that was requested with `request_frame` is signaled, and returns
+    /// its timestamp in milliseconds. Returns `None` right away if no frame was requested.
+    ///
+    /// The events of winit that are read meanwhile are kept for its event loop.
+    pub fn wait_frame(&self) -> Option<u32> {
+        loop {
+            {
+                let mut frame = self.frame.lock().unwrap();
+                frame.receive();
+                if frame.done.is_some() || frame.pending.is_none() {
+                    return frame.done.take();
+                }
+            }
+
+            // the lock isn't held while blocking, so that other threads can request frames
+            if let Err(err) = unsafe { read_events(self.display as *mut wl_display) } {
+                warn!("Couldn't read the Wayland events: {}", err);
+                return None;
+            }
+        }
+    }
+}
+
+impl FrameCallback {
+    /// Takes the `done` events that were dispatched to the queue of the callbacks.
+    fn receive(&mut self) {
+        for event in &mut self.events {
+            if let WaylandEvent::Wayland(WaylandProtocolEvent::WlCallback(_, event)) = event {
+                let WlCallbackEvent::Done(time) = event;
+                self.pending = None;
+                self.done = Some(time);
+            }
+        }
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        // the display belongs to winit or to the user, and dropping a `WlDisplay` disconnects it
+        if let Some(globals) = self.globals.lock().unwrap().take() {
+            mem::forget(globals.env.display);
+        }
+    }
 }
 
 impl GlContext for Window {
@@ -71,13 +478,235 @@ impl GlContext for Window {
         self.context.swap_buffers()
     }
 
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.context.swap_buffers_with_damage(rects)
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.context.set_damage_region(rects)
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        self.context.buffer_age()
+    }
+
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        let mut scaling = self.scaling.lock().unwrap();
+        scaling.size = (width, height);
+        self.update_scaling(&scaling);
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.context.get_reset_status()
+    }
+
+    #[inline]
+    fn get_api(&self) -> ::Api {
+        self.context.get_api()
+    }
+
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.context.get_api_version()
+    }
+
+    #[inline]
+    fn get_pixel_format(&self) -> PixelFormat {
+        self.context.get_pixel_format().clone()
+    }
+}
+
+impl Subsurface {
+    /// Moves the subsurface relative to the top-left corner of its parent, in points.
+    ///
+    /// Like the other properties of the subsurface, this takes effect when the parent's next
+    /// buffer is swapped.
+    #[inline]
+    pub fn set_position(&self, x: i32, y: i32) {
+        self.subsurface.set_position(x, y);
+    }
+
+    /// Restacks the subsurface right above `sibling`, which is either its parent or another of
+    /// its parent's subsurfaces.
+    #[inline]
+    pub fn place_above(&self, sibling: &WlSurface) {
+        self.subsurface.place_above(sibling);
+    }
+
+    /// Restacks the subsurface right below `sibling`, which is either its parent or another of
+    /// its parent's subsurfaces.
+    #[inline]
+    pub fn place_below(&self, sibling: &WlSurface) {
+        self.subsurface.place_below(sibling);
+    }
+
+    /// In synchronized mode, which is the default, the buffers swapped on the subsurface are only
+    /// shown when the parent's next buffer is. In desynchronized mode they are shown right away.
+    #[inline]
+    pub fn set_sync(&self, sync: bool) {
+        if sync {
+            self.subsurface.set_sync();
+        } else {
+            self.subsurface.set_desync();
+        }
+    }
+
+    /// Sets the factor between the size of the buffers in pixels and the size of the surface.
+    /// A scale of 0 is treated as 1.
+    #[inline]
+    pub fn set_buffer_scale(&self, scale: u32) {
+        self.egl_surface.set_buffer_scale(cmp::max(scale, 1) as i32);
+    }
+
+    /// Returns the surface of the subsurface.
+    #[inline]
+    pub fn get_surface(&self) -> &WlSurface {
+        &self.egl_surface
+    }
+}
+
+impl GlContext for Subsurface {
+    #[inline]
+    unsafe fn make_current(&self) -> Result<(), ContextError> {
+        self.context.make_current()
+    }
+
+    #[inline]
+    fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    #[inline]
+    fn get_proc_address(&self, addr: &str) -> *const () {
+        self.context.get_proc_address(addr)
+    }
+
+    #[inline]
+    fn swap_buffers(&self) -> Result<(), ContextError> {
+        self.context.swap_buffers()
+    }
+
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.context.swap_buffers_with_damage(rects)
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.context.set_damage_region(rects)
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        self.context.buffer_age()
+    }
+
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        self.egl_surface.resize(width as i32, height as i32, 0, 0);
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.context.get_reset_status()
+    }
+
     #[inline]
     fn get_api(&self) -> ::Api {
         self.context.get_api()
     }
 
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.context.get_api_version()
+    }
+
     #[inline]
     fn get_pixel_format(&self) -> PixelFormat {
         self.context.get_pixel_format().clone()
     }
 }
+
+/// Creates an EGL context that renders to `egl_surface`.
+fn create_context(library: &Arc<Library>, pf_reqs: &PixelFormatRequirements,
+                  opengl: &GlAttributes<&Window>, display: egl::ffi::EGLNativeDisplayType,
+                  egl_surface: &wegl::WlEglSurface) -> Result<EglContext, CreationError>
+{
+    let egl = ::api::egl::ffi::egl::Egl::load_with(|sym| library.sym(sym));
+    EglContext::new(
+        egl,
+        pf_reqs, &opengl.clone().map_sharing(|_| unimplemented!()),        // TODO: 
+        egl::NativeDisplay::Wayland(Some(display)))
+        .and_then(|p| p.finish(unsafe { egl_surface.egl_surfaceptr() } as *const _))
+        .map_err(|err| {
+            let attempts = err.into_attempts(Backend::Egl, Some(library.path()), opengl.profile);
+            CreationError::AttemptsFailed(attempts)
+        })
+}
+
+/// Returns true if `err` only reports that no config matched the pixel format requirements.
+fn is_missing_config(err: &CreationError) -> bool {
+    match *err {
+        CreationError::AttemptsFailed(ref attempts) => attempts.iter().all(|attempt| {
+            match attempt.error {
+                CreationError::NoAvailablePixelFormat => true,
+                _ => false,
+            }
+        }),
+        _ => false,
+    }
+}
+
+/// Returns the error of a window that can't be rendered to because no EGL library could be
+/// loaded.
+///
+/// winit only picks the display server when it first connects to it, so at this point the
+/// window can't be moved to XWayland anymore and we can only tell the user how to do it.
+fn no_library_error() -> CreationError {
+    let mut message = "No EGL library could be loaded to render on Wayland".to_owned();
+    if env::var_os("DISPLAY").is_some() {
+        message.push_str(", set WINIT_UNIX_BACKEND=x11 before starting the program to use \
+                          XWayland instead");
+    }
+    CreationError::BackendError { backend: Backend::Egl, code: None, message: message }
+}
+
+/// Reads the events of `display` from its socket, waiting for some to arrive, and dispatches
+/// them to their queues.
+///
+/// This goes through `wl_display_prepare_read` so that it can run alongside winit's event loop.
+unsafe fn read_events(display: *mut wl_display) -> io::Result<()> {
+    // the events that another thread already read must be dispatched before reading new ones
+    while ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_prepare_read, display) != 0 {
+        if ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_dispatch_pending, display) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    // the compositor must have received the request of the frame before we wait for it
+    ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_flush, display);
+
+    let mut fd = libc::pollfd {
+        fd: ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_get_fd, display),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    if libc::poll(&mut fd, 1, -1) < 0 {
+        let err = io::Error::last_os_error();
+        ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_cancel_read, display);
+        return if err.kind() == io::ErrorKind::Interrupted { Ok(()) } else { Err(err) };
+    }
+
+    if ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_read_events, display) < 0 ||
+       ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_dispatch_pending, display) < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
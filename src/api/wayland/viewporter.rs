@@ -0,0 +1,123 @@
+//! The `wp_viewporter` protocol, which wayland-client doesn't provide.
+//!
+//! Its interfaces are generated from `protocols/viewporter.xml` by wayland-scanner, and its
+//! requests are sent through the libwayland-client that wayland-client loaded.
+
+use std::ptr;
+use wayland_client::Proxy;
+use wayland_client::wayland::WlRegistry;
+use wayland_client::wayland::compositor::WlSurface;
+use wayland_sys::client::*;
+use wayland_sys::common::*;
+
+use self::interfaces::{wp_viewport_interface, wp_viewporter_interface};
+
+#[allow(dead_code, non_upper_case_globals)]
+mod interfaces {
+    include!(concat!(env!("OUT_DIR"), "/viewporter_interfaces.rs"));
+
+    // `wp_viewporter.get_viewport` takes a `wl_surface`, whose interface is private to
+    // wayland-client. libwayland only looks at the types of the arguments of events, so its
+    // name is enough here.
+    static mut wl_surface_interface: wl_interface = wl_interface {
+        name: b"wl_surface\0" as *const u8 as *const c_char,
+        version: 3,
+        request_count: 0,
+        requests: NULLPTR as *const wl_message,
+        event_count: 0,
+        events: NULLPTR as *const wl_message,
+    };
+}
+
+// the opcodes of the requests
+const WL_REGISTRY_BIND: u32 = 0;
+const WP_VIEWPORTER_DESTROY: u32 = 0;
+const WP_VIEWPORTER_GET_VIEWPORT: u32 = 1;
+const WP_VIEWPORT_DESTROY: u32 = 0;
+const WP_VIEWPORT_SET_SOURCE: u32 = 1;
+const WP_VIEWPORT_SET_DESTINATION: u32 = 2;
+
+/// The `wp_viewporter` global, which creates the viewports of surfaces.
+pub struct WpViewporter {
+    ptr: *mut wl_proxy,
+}
+
+unsafe impl Send for WpViewporter {}
+unsafe impl Sync for WpViewporter {}
+
+impl WpViewporter {
+    /// Binds the `wp_viewporter` global among the `globals` of `registry`, as listed by
+    /// `wayland_env!`, or returns `None` if the compositor doesn't advertise one.
+    pub fn bind(registry: &WlRegistry, globals: &[(u32, String, u32)]) -> Option<WpViewporter> {
+        let name = match globals.iter().find(|g| g.1 == "wp_viewporter") {
+            Some(&(name, _, _)) => name,
+            None => return None,
+        };
+
+        let ptr = unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_marshal_constructor, registry.ptr(),
+                          WL_REGISTRY_BIND, &wp_viewporter_interface as *const wl_interface,
+                          name, wp_viewporter_interface.name, 1u32, ptr::null_mut::<wl_proxy>())
+        };
+        Some(WpViewporter { ptr: ptr })
+    }
+
+    /// Creates the viewport of `surface`, which mustn't have one already.
+    pub fn get_viewport(&self, surface: &WlSurface) -> WpViewport {
+        let ptr = unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_marshal_constructor, self.ptr,
+                          WP_VIEWPORTER_GET_VIEWPORT,
+                          &wp_viewport_interface as *const wl_interface,
+                          ptr::null_mut::<wl_proxy>(), surface.ptr())
+        };
+        WpViewport { ptr: ptr }
+    }
+}
+
+impl Drop for WpViewporter {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_marshal, self.ptr, WP_VIEWPORTER_DESTROY);
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
+        }
+    }
+}
+
+/// The viewport of a surface, which crops and scales its buffers. Dropping it restores the
+/// default behavior of the surface.
+///
+/// Like the other state of the surface, the changes take effect along with its next buffer.
+pub struct WpViewport {
+    ptr: *mut wl_proxy,
+}
+
+unsafe impl Send for WpViewport {}
+unsafe impl Sync for WpViewport {}
+
+impl WpViewport {
+    /// Crops the buffers to the given rectangle, in the coordinates of the surface.
+    pub fn set_source(&self, x: f64, y: f64, width: f64, height: f64) {
+        unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_marshal, self.ptr, WP_VIEWPORT_SET_SOURCE,
+                          wl_fixed_from_double(x), wl_fixed_from_double(y),
+                          wl_fixed_from_double(width), wl_fixed_from_double(height));
+        }
+    }
+
+    /// Scales the buffers to `width` and `height` in the coordinates of the surface.
+    pub fn set_destination(&self, width: i32, height: i32) {
+        unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_marshal, self.ptr,
+                          WP_VIEWPORT_SET_DESTINATION, width, height);
+        }
+    }
+}
+
+impl Drop for WpViewport {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_marshal, self.ptr, WP_VIEWPORT_DESTROY);
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
+        }
+    }
+}
@@ -228,13 +228,17 @@ unsafe fn create_context(extra: Option<(&gl::wgl_extra::Wgl, &PixelFormatRequire
 
             match opengl.version {
                 GlRequest::Latest => {},
-                GlRequest::Specific(Api::OpenGl, (major, minor)) => {
+                // drivers return the newest version that is compatible with the requested one,
+                // so requesting the minimum version is enough for `AtLeast`
+                GlRequest::Specific(Api::OpenGl, (major, minor)) |
+                GlRequest::AtLeast(Api::OpenGl, (major, minor)) => {
                     attributes.push(gl::wgl_extra::CONTEXT_MAJOR_VERSION_ARB as c_int);
                     attributes.push(major as c_int);
                     attributes.push(gl::wgl_extra::CONTEXT_MINOR_VERSION_ARB as c_int);
                     attributes.push(minor as c_int);
                 },
-                GlRequest::Specific(Api::OpenGlEs, (major, minor)) => {
+                GlRequest::Specific(Api::OpenGlEs, (major, minor)) |
+                GlRequest::AtLeast(Api::OpenGlEs, (major, minor)) => {
                     if extensions.split(' ').find(|&i| i == "WGL_EXT_create_context_es2_profile")
                                             .is_some()
                     {
@@ -249,8 +253,11 @@ unsafe fn create_context(extra: Option<(&gl::wgl_extra::Wgl, &PixelFormatRequire
                     attributes.push(gl::wgl_extra::CONTEXT_MINOR_VERSION_ARB as c_int);
                     attributes.push(minor as c_int);
                 },
-                GlRequest::Specific(_, _) => return Err(CreationError::OpenGlVersionNotSupported),
-                GlRequest::GlThenGles { opengl_version: (major, minor), .. } => {
+                GlRequest::Specific(_, _) | GlRequest::AtLeast(_, _) => {
+                    return Err(CreationError::OpenGlVersionNotSupported);
+                },
+                GlRequest::GlThenGles { opengl_version: (major, minor), .. } |
+                GlRequest::AtLeastGlThenGles { opengl_version: (major, minor), .. } => {
                     attributes.push(gl::wgl_extra::CONTEXT_MAJOR_VERSION_ARB as c_int);
                     attributes.push(major as c_int);
                     attributes.push(gl::wgl_extra::CONTEXT_MINOR_VERSION_ARB as c_int);
@@ -306,6 +313,22 @@ unsafe fn create_context(extra: Option<(&gl::wgl_extra::Wgl, &PixelFormatRequire
                     flags = flags | gl::wgl_extra::CONTEXT_DEBUG_BIT_ARB as c_int;
                 }
 
+                // requesting a forward-compatible context below 3.0 is an error
+                if opengl.forward_compatible {
+                    match opengl.version {
+                        GlRequest::Specific(Api::OpenGl, version) |
+                        GlRequest::AtLeast(Api::OpenGl, version) |
+                        GlRequest::GlThenGles { opengl_version: version, .. } |
+                        GlRequest::AtLeastGlThenGles { opengl_version: version, .. }
+                            if version >= (3, 0) =>
+                        {
+                            flags = flags | gl::wgl_extra::CONTEXT_FORWARD_COMPATIBLE_BIT_ARB
+                                            as c_int;
+                        },
+                        _ => (),
+                    }
+                }
+
                 flags
             };
 
@@ -59,7 +59,7 @@ impl Window {
         let context = unsafe {
             let w = winit_window.platform_window() as winapi::HWND;
             match opengl.version {
-                GlRequest::Specific(Api::OpenGlEs, (_major, _minor)) => {
+                GlRequest::Specific(Api::OpenGlEs, _) | GlRequest::AtLeast(Api::OpenGlEs, _) => {
                     if let Some(egl) = egl {
                         if let Ok(c) = EglContext::new(egl.clone(), &pf_reqs, &opengl.clone().map_sharing(|_| unimplemented!()),
                                                        egl::NativeDisplay::Other(Some(ptr::null())))
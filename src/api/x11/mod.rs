@@ -7,77 +7,89 @@ pub mod ffi;
 use CreationError;
 use libc;
 use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::env;
 use std::{mem, ptr};
-use std::sync::{Arc};
+use std::sync::{Arc, Mutex};
 
 use winit;
-use winit::os::unix::WindowExt;
+use winit::os::unix::{WindowBuilderExt, WindowExt};
 
 use Api;
+use Backend;
 use ContextError;
 use GlAttributes;
 use GlContext;
 use GlRequest;
 use PixelFormat;
 use PixelFormatRequirements;
-
-use std::ffi::CString;
+use Rect;
+use ResetStatus;
+use SyncValues;
+use WindowAttributes;
+use os::unix::X11Backend;
+use platform::PlatformSpecificWindowBuilderAttributes;
+use window::build_winit_window;
 
 use api::glx::Context as GlxContext;
 use api::egl;
 use api::egl::Context as EglContext;
 use api::x11::ffi::glx::Glx;
 use api::egl::ffi::egl::Egl;
-use api::dlopen;
+use api::dlopen::{self, Library, EGL_LIBRARIES, EGL_LIBRARY_VAR};
+
+use x11_dl::xrender::Xrender;
+
+/// Environment variable with the paths of the GLX libraries to try first, separated with `:`.
+pub const GLX_LIBRARY_VAR: &'static str = "GLUTIN_GLX_LIBRARY";
+
+/// Environment variable that overrides the backend preference on X11, see `X11Backend`.
+pub const X11_BACKEND_VAR: &'static str = "GLUTIN_X11_BACKEND";
+
+/// The GLX libraries that are tried by default. Under GLVND, `libGLX.so.0` is the
+/// vendor-neutral GLX library, while `libGL.so.1` is the legacy one.
+const GLX_LIBRARIES: &'static [&'static str] = &["libGLX.so.0", "libGL.so.1", "libGL.so"];
+
+/// The GLVND library that exports the OpenGL functions, used along with GLX libraries that
+/// don't, like `libGLX.so.0`.
+const OPENGL_LIBRARY: &'static str = "libOpenGL.so.0";
 
 struct GlxOrEgl {
     glx: Option<Glx>,
+    glx_library: Option<Arc<Library>>,
+    // only loaded along with GLX libraries that don't export the GL functions, like GLVND's
+    // `libGLX.so.0`
+    gl_library: Option<Arc<Library>>,
     egl: Option<Egl>,
+    egl_library: Option<Arc<Library>>,
 }
 
 impl GlxOrEgl {
-    fn new() -> GlxOrEgl {
-        // TODO: use something safer than raw "dlopen"
-        let glx = {
-            let mut libglx = unsafe {
-                dlopen::dlopen(b"libGL.so.1\0".as_ptr() as *const _, dlopen::RTLD_NOW)
-            };
-            if libglx.is_null() {
-                libglx = unsafe {
-                    dlopen::dlopen(b"libGL.so\0".as_ptr() as *const _, dlopen::RTLD_NOW)
-                };
-            }
-            if libglx.is_null() {
-                None
-            } else {
-                Some(Glx::load_with(|sym| {
-                    let sym = CString::new(sym).unwrap();
-                    unsafe { dlopen::dlsym(libglx, sym.as_ptr()) }
-                }))
-            }
-        };
-        // TODO: use something safer than raw "dlopen"
-        let egl = {
-            let mut libegl = unsafe {
-                dlopen::dlopen(b"libEGL.so.1\0".as_ptr() as *const _, dlopen::RTLD_NOW)
-            };
-            if libegl.is_null() {
-                libegl = unsafe {
-                    dlopen::dlopen(b"libEGL.so\0".as_ptr() as *const _, dlopen::RTLD_NOW)
-                };
-            }
-            if libegl.is_null() {
-                None
-            } else {
-                Some(Egl::load_with(|sym| {
-                    let sym = CString::new(sym).unwrap();
-                    unsafe { dlopen::dlsym(libegl, sym.as_ptr()) }
-                }))
-            }
+    fn new(glx_libraries: &[String], egl_libraries: &[String]) -> GlxOrEgl {
+        let glx_library =
+            Library::open_first(&dlopen::candidates(GLX_LIBRARY_VAR, glx_libraries,
+                                                    GLX_LIBRARIES));
+        let glx = glx_library.as_ref().map(|lib| Glx::load_with(|sym| lib.sym(sym)));
+        // whatever its name, a GLX library that doesn't export the core GL functions needs
+        // GLVND's `libOpenGL.so.0` next to it
+        let gl_library = match glx_library {
+            Some(ref lib) if lib.sym("glGetString").is_null() => {
+                Library::open(OPENGL_LIBRARY).ok()
+            },
+            _ => None,
         };
+
+        let egl_library =
+            Library::open_first(&dlopen::candidates(EGL_LIBRARY_VAR, egl_libraries,
+                                                    EGL_LIBRARIES));
+        let egl = egl_library.as_ref().map(|lib| Egl::load_with(|sym| lib.sym(sym)));
+
         GlxOrEgl {
             glx: glx,
+            glx_library: glx_library,
+            gl_library: gl_library,
             egl: egl,
+            egl_library: egl_library,
         }
     }
 }
@@ -90,8 +102,13 @@ enum Context {
 
 pub struct Window {
     display: Arc<XConnection>, // нужен, что бы кое-какие функции для той же карты цветов вызвать
-    colormap: ffi::Colormap,
     context: Context,
+    // kept so that they can still be returned once the context has been destroyed
+    api: Api,
+    pixel_format: PixelFormat,
+    // the libraries must outlive the context, which uses functions loaded from them
+    library: Arc<Library>,
+    gl_library: Option<Arc<Library>>,
 }
 
 unsafe impl Send for Window {}
@@ -103,121 +120,501 @@ impl Drop for Window {
             // we don't call MakeCurrent(0, 0) because we are not sure that the context
             // is still the current one
             self.context = Context::None;
+        }
+    }
+}
+
+/// Builds the error returned when the X server reported `err` after a call to `function`.
+fn x_error(function: &str, err: XError) -> CreationError {
+    CreationError::BackendError {
+        backend: Backend::X11,
+        code: Some(err.error_code as i32),
+        message: format!("{} failed: {}", function, err),
+    }
+}
+
+/// The backends that can create a context on X11.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GlBackend {
+    Glx,
+    Egl,
+}
+
+impl GlBackend {
+    #[inline]
+    fn backend(&self) -> Backend {
+        match *self {
+            GlBackend::Glx => Backend::Glx,
+            GlBackend::Egl => Backend::Egl,
+        }
+    }
+}
+
+/// Returns the backends to try, in order, to create a context for `request`, or `None` if no
+/// backend can create such a context.
+///
+/// The `GLUTIN_X11_BACKEND` environment variable takes precedence over `preference`.
+fn backend_order(preference: Option<X11Backend>, request: &GlRequest)
+                 -> Option<&'static [GlBackend]>
+{
+    let preference = match env::var(X11_BACKEND_VAR) {
+        Ok(value) => match parse_backend(&value) {
+            Some(backend) => Some(backend),
+            None => {
+                warn!("Ignoring unknown value {:?} of {}", value, X11_BACKEND_VAR);
+                preference
+            },
+        },
+        Err(_) => preference,
+    };
+
+    let is_gles = match *request {
+        GlRequest::Latest | GlRequest::Specific(Api::OpenGl, _) |
+        GlRequest::AtLeast(Api::OpenGl, _) | GlRequest::GlThenGles { .. } |
+        GlRequest::AtLeastGlThenGles { .. } => false,
+        GlRequest::Specific(Api::OpenGlEs, _) | GlRequest::AtLeast(Api::OpenGlEs, _) => true,
+        GlRequest::Specific(_, _) | GlRequest::AtLeast(_, _) => return None,
+    };
+
+    const GLX_ONLY: &'static [GlBackend] = &[GlBackend::Glx];
+    const EGL_ONLY: &'static [GlBackend] = &[GlBackend::Egl];
+    const GLX_THEN_EGL: &'static [GlBackend] = &[GlBackend::Glx, GlBackend::Egl];
+    const EGL_THEN_GLX: &'static [GlBackend] = &[GlBackend::Egl, GlBackend::Glx];
+
+    Some(match preference {
+        Some(X11Backend::GlxOnly) => GLX_ONLY,
+        Some(X11Backend::EglOnly) => EGL_ONLY,
+        Some(X11Backend::PreferGlx) => GLX_THEN_EGL,
+        Some(X11Backend::PreferEgl) => EGL_THEN_GLX,
+        // GLX should be preferred over EGL for desktop OpenGL, otherwise crashes may occur on
+        // X11 – issue #314. OpenGL ES goes through GLX with `GLX_EXT_create_context_es2_profile`
+        // only if EGL fails.
+        None if is_gles => EGL_THEN_GLX,
+        None => GLX_THEN_EGL,
+    })
+}
+
+/// Parses the value of the `GLUTIN_X11_BACKEND` environment variable.
+fn parse_backend(value: &str) -> Option<X11Backend> {
+    match value {
+        "glx" => Some(X11Backend::PreferGlx),
+        "egl" => Some(X11Backend::PreferEgl),
+        "glx-only" => Some(X11Backend::GlxOnly),
+        "egl-only" => Some(X11Backend::EglOnly),
+        _ => None,
+    }
+}
+
+/// A context whose config has been chosen, but which isn't associated to a window yet.
+enum Prototype<'a> {
+    Glx(::api::glx::ContextPrototype<'a>),
+    Egl(::api::egl::ContextPrototype<'a>),
+}
+
+impl<'a> Prototype<'a> {
+    fn finish(self, window: ffi::Window) -> Result<Context, CreationError> {
+        match self {
+            Prototype::Glx(p) => p.finish(window).map(Context::Glx),
+            Prototype::Egl(p) => p.finish(window as _).map(Context::Egl),
+        }
+    }
+}
+
+/// Chooses a GLX config, and returns it along with the visual that the window must use.
+fn choose_glx_config<'a>(glx: &Glx, display: &Arc<XConnection>, pf_reqs: &PixelFormatRequirements,
+                         opengl: &'a GlAttributes<&'a GlxContext>, screen_id: libc::c_int,
+                         visual_filter: &Fn(ffi::VisualID) -> bool)
+                         -> Result<(Prototype<'a>, ffi::XVisualInfo), CreationError>
+{
+    let prototype = try!(GlxContext::new(glx.clone(), display, pf_reqs, opengl, screen_id,
+                                         visual_filter));
+    let visual_infos = prototype.get_visual_infos().clone();
+    Ok((Prototype::Glx(prototype), visual_infos))
+}
+
+/// Chooses an EGL config, and returns it along with the visual that the window must use.
+fn choose_egl_config<'a>(egl: &Egl, display: &Arc<XConnection>, pf_reqs: &PixelFormatRequirements,
+                         opengl: &'a GlAttributes<&'a EglContext>,
+                         visual_filter: &Fn(ffi::VisualID) -> bool)
+                         -> Result<(Prototype<'a>, ffi::XVisualInfo), CreationError>
+{
+    let native_display = egl::NativeDisplay::X11(Some(display.display as *const _));
+    let prototype = try!(EglContext::new_with_visual_filter(
+        egl.clone(), pf_reqs, opengl, native_display,
+        &|visual_id| visual_filter(visual_id as ffi::VisualID)));
+
+    let visual_infos = unsafe {
+        let mut template: ffi::XVisualInfo = mem::zeroed();
+        template.visualid = try!(prototype.get_native_visual_id()) as ffi::VisualID;
+
+        let mut num_visuals = 0;
+        let vi = (display.xlib.XGetVisualInfo)(display.display, ffi::VisualIDMask,
+                                               &mut template, &mut num_visuals);
+        try!(display.check_errors().map_err(|e| x_error("XGetVisualInfo", e)));
+        if vi.is_null() || num_visuals < 1 {
+            return Err(CreationError::NoAvailablePixelFormat);
+        }
+
+        let vi_copy = ptr::read(vi as *const _);
+        (display.xlib.XFree)(vi as *mut _);
+        vi_copy
+    };
+
+    Ok((Prototype::Egl(prototype), visual_infos))
+}
+
+/// Returns whether the visual `visual_id` has an alpha channel, which is what makes a window
+/// transparent when a compositing manager is running.
+fn is_argb_visual(display: &XConnection, xrender: &Xrender, visual_id: ffi::VisualID) -> bool {
+    unsafe {
+        let mut template: ffi::XVisualInfo = mem::zeroed();
+        template.visualid = visual_id;
 
-            (self.display.xlib.XFreeColormap)(self.display.display, self.colormap);
+        let mut num_visuals = 0;
+        let vi = (display.xlib.XGetVisualInfo)(display.display, ffi::VisualIDMask,
+                                               &mut template, &mut num_visuals);
+        if vi.is_null() {
+            return false;
         }
+
+        let format = (xrender.XRenderFindVisualFormat)(display.display, (*vi).visual);
+        (display.xlib.XFree)(vi as *mut _);
+        !format.is_null() && (*format).direct.alphaMask > 0
+    }
+}
+
+/// Returns the visual of `window` and the number of its screen.
+fn get_window_visual(display: &XConnection, window: ffi::Window)
+                     -> Result<(ffi::VisualID, libc::c_int), CreationError>
+{
+    unsafe {
+        let mut attributes: ffi::XWindowAttributes = mem::zeroed();
+        (display.xlib.XGetWindowAttributes)(display.display, window, &mut attributes);
+        try!(display.check_errors().map_err(|e| x_error("XGetWindowAttributes", e)));
+        Ok(((display.xlib.XVisualIDFromVisual)(attributes.visual),
+            (display.xlib.XScreenNumberOfScreen)(attributes.screen)))
     }
 }
 
+type XErrorHandler = Option<unsafe extern "C" fn(*mut ffi::Display, *mut ffi::XErrorEvent)
+                                                  -> libc::c_int>;
+
+lazy_static! {
+    // The connections to displays that are owned by the users of `Window::new_raw`, by address of
+    // the display. They are never dropped, as dropping an `XConnection` closes its display.
+    static ref FOREIGN_CONNECTIONS: Mutex<HashMap<usize, Arc<XConnection>>> =
+        Mutex::new(HashMap::new());
+
+    // The error handler that was installed before `foreign_error_handler`, which handles the
+    // errors of the other displays.
+    static ref PREVIOUS_ERROR_HANDLER: Mutex<XErrorHandler> = Mutex::new(None);
+
+    // Held from the moment `foreign_error_handler` is installed until the previous handler is
+    // restored. The error handler of Xlib is global, so two threads doing this at once would
+    // restore each other's handler in the middle of a creation.
+    static ref ERROR_HANDLER_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Returns a connection that uses `display`, a display opened by someone else, without ever
+/// closing it.
+unsafe fn foreign_connection(display: *mut ffi::Display)
+                             -> Result<Arc<XConnection>, CreationError>
+{
+    let mut connections = FOREIGN_CONNECTIONS.lock().unwrap();
+    if let Some(connection) = connections.get(&(display as usize)) {
+        return Ok(connection.clone());
+    }
+
+    let open_error = |err: ffi::OpenError| CreationError::BackendError {
+        backend: Backend::X11,
+        code: None,
+        message: format!("Failed to load the X libraries: {}", err),
+    };
+
+    let connection = Arc::new(XConnection {
+        xlib: try!(ffi::Xlib::open().map_err(&open_error)),
+        xf86vmode: try!(ffi::Xf86vmode::open().map_err(&open_error)),
+        xcursor: try!(ffi::Xcursor::open().map_err(&open_error)),
+        xinput2: try!(ffi::XInput2::open().map_err(&open_error)),
+        display: display,
+        latest_error: Mutex::new(None),
+    });
+    connections.insert(display as usize, connection.clone());
+    Ok(connection)
+}
+
+/// Stores the errors of the displays of `FOREIGN_CONNECTIONS` in their connection, so that
+/// `check_errors` returns them, and passes the other ones to the previous handler.
+unsafe extern "C" fn foreign_error_handler(display: *mut ffi::Display,
+                                           event: *mut ffi::XErrorEvent) -> libc::c_int
+{
+    let connection = FOREIGN_CONNECTIONS.lock().unwrap().get(&(display as usize)).cloned();
+    let connection = match connection {
+        Some(connection) => connection,
+        None => return match *PREVIOUS_ERROR_HANDLER.lock().unwrap() {
+            Some(handler) => handler(display, event),
+            None => 0,
+        },
+    };
+
+    let mut buffer: [libc::c_char; 1024] = mem::zeroed();
+    (connection.xlib.XGetErrorText)(display, (*event).error_code as libc::c_int,
+                                    buffer.as_mut_ptr(), buffer.len() as libc::c_int);
+    let description = ::std::ffi::CStr::from_ptr(buffer.as_ptr()).to_string_lossy();
+
+    *connection.latest_error.lock().unwrap() = Some(XError {
+        description: description.into_owned(),
+        error_code: (*event).error_code,
+        request_code: (*event).request_code,
+        minor_code: (*event).minor_code,
+    });
+    0
+}
+
 impl Window {
+    /// Creates a context for `winit_window`, and returns it along with the window.
+    ///
+    /// If `winit_window` is `None`, the config of the context is chosen first, and the window is
+    /// then created with the visual of this config, which has an alpha channel if the window is
+    /// transparent. Otherwise the config is chosen among the ones whose visual is the one of the
+    /// window, as the X server would fail with `BadMatch` when the context is made current.
     pub fn new(
+        window: &WindowAttributes,
         pf_reqs: &PixelFormatRequirements,
         opengl: &GlAttributes<&Window>,
-        winit_window: &winit::Window,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+        winit_window: Option<winit::Window>,
+    ) -> Result<(Window, winit::Window), CreationError> {
+        Window::create(window, pf_reqs, opengl, pl_attribs, winit_window, None)
+            .map(|(window, winit_window)| (window, winit_window.unwrap()))
+    }
+
+    /// Creates a context for `window`, a window of `display` that wasn't created by winit.
+    ///
+    /// The context is created on `display`, which stays owned by the caller and must outlive
+    /// it. The config is chosen among the ones whose visual is the one of the window.
+    ///
+    /// The X errors of `display` are caught while the context is created, by an error handler
+    /// that is installed for that time only.
+    pub unsafe fn new_raw(
+        display: *mut ffi::Display,
+        window: ffi::Window,
+        pf_reqs: &PixelFormatRequirements,
+        opengl: &GlAttributes<&Window>,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
     ) -> Result<Window, CreationError> {
-        let display = winit_window.get_xlib_xconnection().unwrap();
-        let screen_id = winit_window.get_xlib_screen_id().unwrap() as _;
+        let connection = try!(foreign_connection(display));
 
-        // start the context building process
-        enum Prototype<'a> {
-            Glx(::api::glx::ContextPrototype<'a>),
-            Egl(::api::egl::ContextPrototype<'a>),
-        }
-        let builder_clone_opengl_glx = opengl.clone().map_sharing(|_| unimplemented!());      // FIXME:
-        let builder_clone_opengl_egl = opengl.clone().map_sharing(|_| unimplemented!());      // FIXME:
-        let backend = GlxOrEgl::new();
-        let context = match opengl.version {
-            GlRequest::Latest | GlRequest::Specific(Api::OpenGl, _) | GlRequest::GlThenGles { .. } => {
-                // GLX should be preferred over EGL, otherwise crashes may occur
-                // on X11 – issue #314
-                if let Some(ref glx) = backend.glx {
-                    Prototype::Glx(try!(GlxContext::new(
-                        glx.clone(),
-                        &display.xlib,
-                        pf_reqs,
-                        &builder_clone_opengl_glx,
-                        display.display,
-                        screen_id,
-                    )))
-                } else if let Some(ref egl) = backend.egl {
-                    Prototype::Egl(try!(EglContext::new(
-                            egl.clone(),
-                        pf_reqs,
-                        &builder_clone_opengl_egl,
-                        egl::NativeDisplay::X11(Some(display.display as *const _)),
-                    )))
-                } else {
-                    return Err(CreationError::NotSupported);
-                }
-            },
-            GlRequest::Specific(Api::OpenGlEs, _) => {
-                if let Some(ref egl) = backend.egl {
-                    Prototype::Egl(try!(EglContext::new(
-                        egl.clone(),
-                        pf_reqs,
-                        &builder_clone_opengl_egl,
-                        egl::NativeDisplay::X11(Some(display.display as *const _)),
-                    )))
-                } else {
-                    return Err(CreationError::NotSupported);
-                }
-            },
-            GlRequest::Specific(_, _) => {
-                return Err(CreationError::NotSupported);
+        let _guard = ERROR_HANDLER_LOCK.lock().unwrap();
+        let previous_handler = (connection.xlib.XSetErrorHandler)(Some(foreign_error_handler));
+        *PREVIOUS_ERROR_HANDLER.lock().unwrap() = previous_handler;
+
+        let result = Window::create(&WindowAttributes::default(), pf_reqs, opengl, pl_attribs,
+                                    None, Some((connection.clone(), window)));
+
+        (connection.xlib.XSetErrorHandler)(previous_handler);
+        result.map(|(window, _)| window)
+    }
+
+    /// Creates a context for `raw_window`, a window and the connection to its display, if it is
+    /// `Some`, for `winit_window` if it is `Some`, or for a new winit window otherwise. Returns
+    /// the winit window, if any, along with the context.
+    fn create(
+        window: &WindowAttributes,
+        pf_reqs: &PixelFormatRequirements,
+        opengl: &GlAttributes<&Window>,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+        winit_window: Option<winit::Window>,
+        raw_window: Option<(Arc<XConnection>, ffi::Window)>,
+    ) -> Result<(Window, Option<winit::Window>), CreationError> {
+        let mut winit_window = winit_window;
+
+        let display = match (winit_window.as_ref(), raw_window.as_ref()) {
+            (Some(w), _) => w.get_xlib_xconnection().unwrap(),
+            (None, Some(&(ref display, _))) => display.clone(),
+            (None, None) => match winit::os::unix::get_x11_xconnection() {
+                Some(display) => display,
+                None => return Err(CreationError::NotSupported),
             },
         };
+        let raw_window = raw_window.map(|(_, w)| w);
+        let existing_window = match winit_window {
+            Some(ref w) => Some(w.get_xlib_window().unwrap() as ffi::Window),
+            None => raw_window,
+        };
 
-        // getting the `visual_infos` (a struct that contains information about the visual to use)
-        let visual_infos = match context {
-            Prototype::Glx(ref p) => p.get_visual_infos().clone(),
-            Prototype::Egl(ref p) => {
-                unsafe {
-                    let mut template: ffi::XVisualInfo = mem::zeroed();
-                    template.visualid = p.get_native_visual_id() as ffi::VisualID;
-
-                    let mut num_visuals = 0;
-                    let vi = (display.xlib.XGetVisualInfo)(display.display, ffi::VisualIDMask,
-                                                           &mut template, &mut num_visuals);
-                    display.check_errors().expect("Failed to call XGetVisualInfo");
-                    assert!(!vi.is_null());
-                    assert!(num_visuals == 1);
-
-                    let vi_copy = ptr::read(vi as *const _);
-                    (display.xlib.XFree)(vi as *mut _);
-                    vi_copy
-                }
+        // the visual that the config must match, once there is a window
+        let (mut window_visual, screen_id) = match existing_window {
+            Some(w) => {
+                let (visual, screen_id) = try!(get_window_visual(&display, w));
+                (Some(visual), screen_id)
             },
+            None => (None, unsafe { (display.xlib.XDefaultScreen)(display.display) }),
         };
 
-        let xlib_window = winit_window.get_xlib_window().unwrap();
-        // finish creating the OpenGL context
-        let context = match context {
-            Prototype::Glx(ctxt) => {
-                Context::Glx(try!(ctxt.finish(xlib_window as _)))
-            },
-            Prototype::Egl(ctxt) => {
-                Context::Egl(try!(ctxt.finish(xlib_window)))
-            },
+        let builder_clone_opengl_glx = opengl.clone().map_sharing(|_| unimplemented!());      // FIXME:
+        let builder_clone_opengl_egl = opengl.clone().map_sharing(|_| unimplemented!());      // FIXME:
+        let backend = GlxOrEgl::new(&pl_attribs.glx_libraries, &pl_attribs.egl_libraries);
+
+        let order = match backend_order(pl_attribs.x11_backend, &opengl.version) {
+            Some(order) => order,
+            None => return Err(CreationError::NotSupported),
         };
 
-        // getting the root window
-        let root = unsafe { (display.xlib.XDefaultRootWindow)(display.display) };
-        display.check_errors().expect("Failed to get root window");
-
-        // creating the color map
-        let cmap = unsafe {
-            let cmap = (display.xlib.XCreateColormap)(display.display, root,
-                                                      visual_infos.visual as *mut _,
-                                                      ffi::AllocNone);
-            display.check_errors().expect("Failed to call XCreateColormap");
-            cmap
+        // transparent windows need a visual with an alpha channel, which can only be chosen if
+        // the window is created here
+        let xrender = if window.transparent && existing_window.is_none() {
+            match Xrender::open() {
+                Ok(xrender) => Some(xrender),
+                Err(err) => {
+                    warn!("Failed to load Xrender, the window will be opaque: {}", err);
+                    None
+                },
+            }
+        } else {
+            None
         };
+        let argb_filter = |visual_id: ffi::VisualID| {
+            is_argb_visual(&display, xrender.as_ref().unwrap(), visual_id)
+        };
+
+        // each backend is tried in turn until one of them succeeds, and the errors list all the
+        // attempts that were made, with the library that was used
+        let mut attempts = Vec::new();
+        let mut created = None;
+        // a window that we create is only kept if its context could be created, so that the
+        // next backend can create one with the visual of its own config
+        let create_window = winit_window.is_none() && raw_window.is_none();
 
-        Ok(Window {
+        for &gl_backend in order {
+            let library = match gl_backend {
+                GlBackend::Glx => backend.glx_library.as_ref(),
+                GlBackend::Egl => backend.egl_library.as_ref(),
+            };
+            let choose = |visual_filter: &Fn(ffi::VisualID) -> bool| match gl_backend {
+                GlBackend::Glx => backend.glx.as_ref().map(|glx| {
+                    choose_glx_config(glx, &display, pf_reqs, &builder_clone_opengl_glx,
+                                      screen_id, visual_filter)
+                }),
+                GlBackend::Egl => backend.egl.as_ref().map(|egl| {
+                    choose_egl_config(egl, &display, pf_reqs, &builder_clone_opengl_egl,
+                                      visual_filter)
+                }),
+            };
+
+            // once there is a window, only the configs of its visual can render to it
+            let required_visual = window_visual;
+            let visual_filter = |visual_id: ffi::VisualID| {
+                required_visual.map(|v| v == visual_id).unwrap_or(true)
+            };
+
+            let prototype = if xrender.is_some() {
+                match choose(&|visual_id| visual_filter(visual_id) && argb_filter(visual_id)) {
+                    Some(Err(CreationError::NoAvailablePixelFormat)) if !opengl.strict => {
+                        warn!("No config has a visual with an alpha channel, the window will \
+                               be opaque");
+                        choose(&visual_filter)
+                    },
+                    Some(Err(CreationError::NoAvailablePixelFormat)) => {
+                        Some(Err(CreationError::BackendError {
+                            backend: Backend::X11,
+                            code: None,
+                            message: format!("No config has a visual with an alpha channel, \
+                                              which is required for transparency"),
+                        }))
+                    },
+                    prototype => prototype,
+                }
+            } else {
+                choose(&visual_filter)
+            };
+
+            let result = match prototype {
+                Some(Ok((prototype, visual_infos))) => {
+                    // a window that can't be built only fails this backend
+                    let built = if create_window {
+                        let builder = winit::WindowBuilder::new()
+                                          .with_x11_visual(&visual_infos as *const _)
+                                          .with_x11_screen(screen_id);
+                        build_winit_window(window, builder).map(|w| {
+                            winit_window = Some(w);
+                            window_visual = Some(visual_infos.visualid);
+                        })
+                    } else {
+                        Ok(())
+                    };
+
+                    built.and_then(|()| {
+                        let xlib_window = match winit_window {
+                            Some(ref w) => w.get_xlib_window().unwrap() as ffi::Window,
+                            None => raw_window.unwrap(),
+                        };
+                        prototype.finish(xlib_window)
+                    })
+                },
+                Some(Err(err)) => Err(err),
+                None => Err(CreationError::BackendError {
+                    backend: gl_backend.backend(),
+                    code: None,
+                    message: format!("No {:?} library could be loaded", gl_backend),
+                }),
+            };
+
+            match result {
+                Ok(context) => {
+                    created = Some((context, library.unwrap().clone()));
+                    break;
+                },
+                Err(err) => {
+                    attempts.extend(err.into_attempts(gl_backend.backend(),
+                                                      library.map(|l| l.path()),
+                                                      opengl.profile));
+                    if create_window {
+                        winit_window = None;
+                        window_visual = None;
+                    }
+                },
+            }
+
+            // the attempts that failed may have left X errors behind
+            let _ = display.check_errors();
+        }
+
+        let (context, library) = match created {
+            Some(created) => created,
+            None => return Err(CreationError::AttemptsFailed(attempts)),
+        };
+        let (api, pixel_format) = match context {
+            Context::Glx(ref ctxt) => (ctxt.get_api(), ctxt.get_pixel_format()),
+            Context::Egl(ref ctxt) => (ctxt.get_api(), ctxt.get_pixel_format()),
+            Context::None => unreachable!(),
+        };
+        info!("Created an OpenGL context with {}", library.path());
+        let gl_library = match context {
+            Context::Glx(_) => backend.gl_library.clone(),
+            _ => None,
+        };
+
+        // the attempts at creating a context with a version that the driver doesn't support
+        // leave X errors behind
+        let _ = display.check_errors();
+
+        let window = Window {
             display: display.clone(),
             context: context,
-            colormap: cmap,
-        })
+            api: api,
+            pixel_format: pixel_format,
+            gl_library: gl_library,
+            library: library,
+        };
+
+        Ok((window, winit_window))
+    }
+
+    /// Returns the path of the GLX or EGL library that was loaded to create the context.
+    #[inline]
+    pub fn get_library_path(&self) -> &str {
+        self.library.path()
     }
 }
 
@@ -236,14 +633,20 @@ impl GlContext for Window {
         match self.context {
             Context::Glx(ref ctxt) => ctxt.is_current(),
             Context::Egl(ref ctxt) => ctxt.is_current(),
-            Context::None => panic!()
+            Context::None => false
         }
     }
 
     #[inline]
     fn get_proc_address(&self, addr: &str) -> *const () {
         match self.context {
-            Context::Glx(ref ctxt) => ctxt.get_proc_address(addr),
+            Context::Glx(ref ctxt) => {
+                let addr_ptr = ctxt.get_proc_address(addr);
+                match self.gl_library {
+                    Some(ref lib) if addr_ptr.is_null() => lib.sym(addr) as *const _,
+                    _ => addr_ptr,
+                }
+            },
             Context::Egl(ref ctxt) => ctxt.get_proc_address(addr),
             Context::None => ptr::null()
         }
@@ -258,12 +661,86 @@ impl GlContext for Window {
         }
     }
 
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.swap_buffers_with_damage(rects),
+            Context::Egl(ref ctxt) => ctxt.swap_buffers_with_damage(rects),
+            Context::None => Ok(())
+        }
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.set_damage_region(rects),
+            Context::Egl(ref ctxt) => ctxt.set_damage_region(rects),
+            Context::None => Ok(())
+        }
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.buffer_age(),
+            Context::Egl(ref ctxt) => ctxt.buffer_age(),
+            Context::None => None
+        }
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.get_reset_status(),
+            Context::Egl(ref ctxt) => ctxt.get_reset_status(),
+            Context::None => ResetStatus::NoError
+        }
+    }
+
+    #[inline]
+    fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.get_sync_values(),
+            Context::Egl(ref ctxt) => ctxt.get_sync_values(),
+            Context::None => Err(ContextError::FunctionUnavailable)
+        }
+    }
+
+    #[inline]
+    fn swap_buffers_msc(&self, target_msc: i64, divisor: i64, remainder: i64)
+                        -> Result<i64, ContextError>
+    {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.swap_buffers_msc(target_msc, divisor, remainder),
+            Context::Egl(ref ctxt) => ctxt.swap_buffers_msc(target_msc, divisor, remainder),
+            Context::None => Err(ContextError::FunctionUnavailable)
+        }
+    }
+
+    #[inline]
+    fn wait_for_sbc(&self, target_sbc: i64) -> Result<SyncValues, ContextError> {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.wait_for_sbc(target_sbc),
+            Context::Egl(ref ctxt) => ctxt.wait_for_sbc(target_sbc),
+            Context::None => Err(ContextError::FunctionUnavailable)
+        }
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         match self.context {
             Context::Glx(ref ctxt) => ctxt.get_api(),
             Context::Egl(ref ctxt) => ctxt.get_api(),
-            Context::None => panic!()
+            Context::None => self.api
+        }
+    }
+
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        match self.context {
+            Context::Glx(ref ctxt) => ctxt.get_api_version(),
+            Context::Egl(ref ctxt) => ctxt.get_api_version(),
+            Context::None => None
         }
     }
 
@@ -272,7 +749,7 @@ impl GlContext for Window {
         match self.context {
             Context::Glx(ref ctxt) => ctxt.get_pixel_format(),
             Context::Egl(ref ctxt) => ctxt.get_pixel_format(),
-            Context::None => panic!()
+            Context::None => self.pixel_format.clone()
         }
     }
 }
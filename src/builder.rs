@@ -0,0 +1,136 @@
+//! The setters shared by the builders of contexts.
+
+/// Implements the setters of the OpenGL attributes and of the pixel format on `$builder`, whose
+/// `opengl` and `pf_reqs` fields hold them.
+macro_rules! context_builder_setters {
+    ($builder:ty) => (
+        /// Sets how the backend should choose the OpenGL API and version.
+        #[inline]
+        pub fn with_gl(mut self, request: $crate::GlRequest) -> $builder {
+            self.opengl.version = request;
+            self
+        }
+
+        /// Sets the desired OpenGL context profile.
+        #[inline]
+        pub fn with_gl_profile(mut self, profile: $crate::GlProfile) -> $builder {
+            self.opengl.profile = Some(profile);
+            self
+        }
+
+        /// Requests a forward-compatible OpenGL context, in which the deprecated features are
+        /// removed. Only applies to OpenGL 3.0 and later.
+        #[inline]
+        pub fn with_gl_forward_compatible(mut self, forward_compatible: bool) -> $builder {
+            self.opengl.forward_compatible = forward_compatible;
+            self
+        }
+
+        /// Sets the *debug* flag for the OpenGL context.
+        ///
+        /// The default value for this flag is `cfg!(debug_assertions)`, which means that it's
+        /// enabled when you run `cargo build` and disabled when you run `cargo build --release`.
+        #[inline]
+        pub fn with_gl_debug_flag(mut self, flag: bool) -> $builder {
+            self.opengl.debug = flag;
+            self
+        }
+
+        /// Sets where the messages of the `GL_KHR_debug` extension are delivered.
+        ///
+        /// This only has an effect if the *debug* flag is set. The callback is installed the
+        /// first time the context is made current.
+        #[inline]
+        pub fn with_gl_debug_output(mut self, output: $crate::DebugOutput) -> $builder {
+            self.opengl.debug_output = Some(output);
+            self
+        }
+
+        /// Sets the robustness of the OpenGL context. See the docs of `Robustness`.
+        #[inline]
+        pub fn with_gl_robustness(mut self, robustness: $crate::Robustness) -> $builder {
+            self.opengl.robustness = robustness;
+            self
+        }
+
+        /// Sets what happens to the pending commands when the context stops being current.
+        #[inline]
+        pub fn with_gl_release_behavior(mut self, behavior: $crate::ReleaseBehavior)
+                                        -> $builder
+        {
+            self.pf_reqs.release_behavior = behavior;
+            self
+        }
+
+        /// Requests that the context has vsync enabled.
+        #[inline]
+        pub fn with_vsync(mut self) -> $builder {
+            self.opengl.vsync = true;
+            self
+        }
+
+        /// Sets whether the pixel format must be hardware-accelerated. `None` means "I don't
+        /// care". The default is `Some(true)`.
+        #[inline]
+        pub fn with_hardware_acceleration(mut self, accelerated: Option<bool>) -> $builder {
+            self.pf_reqs.hardware_accelerated = accelerated;
+            self
+        }
+
+        /// Sets whether the pixel format must be double-buffered. `None` means "I don't care",
+        /// which is the default.
+        #[inline]
+        pub fn with_double_buffer(mut self, double_buffer: Option<bool>) -> $builder {
+            self.pf_reqs.double_buffer = double_buffer;
+            self
+        }
+
+        /// Sets the multisampling level to request.
+        ///
+        /// # Panic
+        ///
+        /// Will panic if `samples` is not a power of two.
+        #[inline]
+        pub fn with_multisampling(mut self, samples: u16) -> $builder {
+            assert!(samples.is_power_of_two());
+            self.pf_reqs.multisampling = Some(samples);
+            self
+        }
+
+        /// Sets the number of bits in the depth buffer.
+        #[inline]
+        pub fn with_depth_buffer(mut self, bits: u8) -> $builder {
+            self.pf_reqs.depth_bits = Some(bits);
+            self
+        }
+
+        /// Sets the number of bits in the stencil buffer.
+        #[inline]
+        pub fn with_stencil_buffer(mut self, bits: u8) -> $builder {
+            self.pf_reqs.stencil_bits = Some(bits);
+            self
+        }
+
+        /// Sets the number of bits in the color buffer.
+        #[inline]
+        pub fn with_pixel_format(mut self, color_bits: u8, alpha_bits: u8) -> $builder {
+            self.pf_reqs.color_bits = Some(color_bits);
+            self.pf_reqs.alpha_bits = Some(alpha_bits);
+            self
+        }
+
+        /// Request the backend to be stereoscopic.
+        #[inline]
+        pub fn with_stereoscopy(mut self) -> $builder {
+            self.pf_reqs.stereoscopy = true;
+            self
+        }
+
+        /// Sets whether sRGB should be enabled on the context. `None` means "I don't care".
+        #[inline]
+        pub fn with_srgb(mut self, srgb_enabled: Option<bool>) -> $builder {
+            self.pf_reqs.srgb = srgb_enabled.unwrap_or(false);
+            self
+        }
+    )
+}
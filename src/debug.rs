@@ -0,0 +1,314 @@
+//! Delivery of the messages of the `GL_KHR_debug` extension.
+//!
+//! The callback is installed the first time the context is made current, which is the earliest
+//! point where the debug functions can be called.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{mem, ptr, slice};
+
+/// The part of the system that emitted a debug message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugSource {
+    Api,
+    WindowSystem,
+    ShaderCompiler,
+    ThirdParty,
+    Application,
+    Other,
+}
+
+/// The kind of event that a debug message describes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugType {
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+
+/// The importance of a debug message, from the least to the most important.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+/// A message emitted by the OpenGL implementation.
+#[derive(Debug, Clone)]
+pub struct DebugMessage {
+    pub source: DebugSource,
+    pub ty: DebugType,
+    /// Implementation-specific identifier of the message.
+    pub id: u32,
+    pub severity: DebugSeverity,
+    pub message: String,
+}
+
+/// Describes where the messages of a debug context should be delivered and which ones should be
+/// dropped.
+///
+/// # Example
+///
+/// ```ignore
+/// let output = DebugOutput::log().min_severity(DebugSeverity::Low).ignore_id(131185);
+/// let window = WindowBuilder::new().with_gl_debug_flag(true)
+///                                  .with_gl_debug_output(output)
+///                                  .build().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct DebugOutput {
+    callback: Arc<Fn(&DebugMessage) + Send + Sync>,
+    min_severity: DebugSeverity,
+    ignored_sources: Vec<DebugSource>,
+    ignored_types: Vec<DebugType>,
+    ignored_ids: Vec<u32>,
+    synchronous: bool,
+}
+
+impl DebugOutput {
+    /// Delivers the messages to a closure.
+    ///
+    /// Unless `synchronous` is set, the driver may call the closure from any thread, which is
+    /// why it must be `Send` and `Sync`.
+    pub fn callback<F>(callback: F) -> DebugOutput
+        where F: Fn(&DebugMessage) + Send + Sync + 'static
+    {
+        DebugOutput {
+            callback: Arc::new(callback),
+            min_severity: DebugSeverity::Notification,
+            ignored_sources: Vec::new(),
+            ignored_types: Vec::new(),
+            ignored_ids: Vec::new(),
+            synchronous: false,
+        }
+    }
+
+    /// Delivers the messages to the `log` crate, with the `gl` target.
+    ///
+    /// High severity messages are logged as errors, medium as warnings, low as infos and
+    /// notifications as debug messages.
+    pub fn log() -> DebugOutput {
+        DebugOutput::callback(|msg: &DebugMessage| {
+            let level = match msg.severity {
+                DebugSeverity::High => ::log::LogLevel::Error,
+                DebugSeverity::Medium => ::log::LogLevel::Warn,
+                DebugSeverity::Low => ::log::LogLevel::Info,
+                DebugSeverity::Notification => ::log::LogLevel::Debug,
+            };
+
+            log!(target: "gl", level, "[{:?}/{:?}] {} (id: {})", msg.source, msg.ty, msg.message,
+                 msg.id);
+        })
+    }
+
+    /// Drops the messages whose severity is lower than `severity`.
+    ///
+    /// The default is `Notification`, which means that all the messages are delivered.
+    #[inline]
+    pub fn min_severity(mut self, severity: DebugSeverity) -> DebugOutput {
+        self.min_severity = severity;
+        self
+    }
+
+    /// Drops the messages emitted by `source`.
+    #[inline]
+    pub fn ignore_source(mut self, source: DebugSource) -> DebugOutput {
+        self.ignored_sources.push(source);
+        self
+    }
+
+    /// Drops the messages of the type `ty`.
+    #[inline]
+    pub fn ignore_type(mut self, ty: DebugType) -> DebugOutput {
+        self.ignored_types.push(ty);
+        self
+    }
+
+    /// Drops the messages with the identifier `id`.
+    #[inline]
+    pub fn ignore_id(mut self, id: u32) -> DebugOutput {
+        self.ignored_ids.push(id);
+        self
+    }
+
+    /// If true, the messages are delivered from the thread that made the faulty call, before
+    /// the call returns. This is slower, but makes it possible to find the faulty call with a
+    /// backtrace.
+    ///
+    /// The default is `false`.
+    #[inline]
+    pub fn synchronous(mut self, synchronous: bool) -> DebugOutput {
+        self.synchronous = synchronous;
+        self
+    }
+
+    #[inline]
+    fn accepts(&self, msg: &DebugMessage) -> bool {
+        msg.severity >= self.min_severity &&
+        !self.ignored_sources.contains(&msg.source) &&
+        !self.ignored_types.contains(&msg.ty) &&
+        !self.ignored_ids.contains(&msg.id)
+    }
+}
+
+/// Owns the debug output of a context and installs it once the context is current.
+pub struct DebugOutputState {
+    // boxed so that the pointer passed to the driver stays valid when the state is moved
+    output: Box<DebugOutput>,
+    installed: AtomicBool,
+}
+
+impl DebugOutputState {
+    #[inline]
+    pub fn new(output: DebugOutput) -> DebugOutputState {
+        DebugOutputState {
+            output: Box::new(output),
+            installed: AtomicBool::new(false),
+        }
+    }
+
+    /// Installs the callback if this hasn't been done yet. The context must be current.
+    ///
+    /// Does nothing if the implementation doesn't support `GL_KHR_debug`.
+    pub unsafe fn install_if_needed<F>(&self, get_proc_address: F)
+        where F: Fn(&str) -> *const ()
+    {
+        if self.installed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let load = |names: &[&str]| {
+            names.iter().map(|n| get_proc_address(n)).find(|p| !p.is_null())
+        };
+
+        let callback = match load(&["glDebugMessageCallback", "glDebugMessageCallbackKHR"]) {
+            Some(f) => mem::transmute::<_, DebugMessageCallbackFn>(f),
+            None => return,
+        };
+        let control = load(&["glDebugMessageControl", "glDebugMessageControlKHR"])
+                          .map(|f| mem::transmute::<_, DebugMessageControlFn>(f));
+        let enable = load(&["glEnable"]).map(|f| mem::transmute::<_, EnableFn>(f));
+
+        if let Some(enable) = enable {
+            enable(DEBUG_OUTPUT);
+            if self.output.synchronous {
+                enable(DEBUG_OUTPUT_SYNCHRONOUS);
+            }
+        }
+
+        // letting the driver drop the messages with a low severity is cheaper than filtering
+        // them in the callback
+        if let Some(control) = control {
+            let severities = [
+                (DebugSeverity::Notification, DEBUG_SEVERITY_NOTIFICATION),
+                (DebugSeverity::Low, DEBUG_SEVERITY_LOW),
+                (DebugSeverity::Medium, DEBUG_SEVERITY_MEDIUM),
+            ];
+
+            for &(severity, value) in severities.iter() {
+                if severity < self.output.min_severity {
+                    control(DONT_CARE, DONT_CARE, value, 0, ptr::null(), 0);
+                }
+            }
+        }
+
+        callback(debug_callback, &*self.output as *const DebugOutput as *const c_void);
+    }
+}
+
+type DebugProc = extern "system" fn(c_uint, c_uint, c_uint, c_uint, c_int, *const c_char,
+                                    *const c_void);
+type DebugMessageCallbackFn = extern "system" fn(DebugProc, *const c_void);
+type DebugMessageControlFn = extern "system" fn(c_uint, c_uint, c_uint, c_int, *const c_uint,
+                                                u8);
+type EnableFn = extern "system" fn(c_uint);
+
+const DONT_CARE: c_uint = 0x1100;
+const DEBUG_OUTPUT: c_uint = 0x92E0;
+const DEBUG_OUTPUT_SYNCHRONOUS: c_uint = 0x8242;
+
+const DEBUG_SOURCE_API: c_uint = 0x8246;
+const DEBUG_SOURCE_WINDOW_SYSTEM: c_uint = 0x8247;
+const DEBUG_SOURCE_SHADER_COMPILER: c_uint = 0x8248;
+const DEBUG_SOURCE_THIRD_PARTY: c_uint = 0x8249;
+const DEBUG_SOURCE_APPLICATION: c_uint = 0x824A;
+
+const DEBUG_TYPE_ERROR: c_uint = 0x824C;
+const DEBUG_TYPE_DEPRECATED_BEHAVIOR: c_uint = 0x824D;
+const DEBUG_TYPE_UNDEFINED_BEHAVIOR: c_uint = 0x824E;
+const DEBUG_TYPE_PORTABILITY: c_uint = 0x824F;
+const DEBUG_TYPE_PERFORMANCE: c_uint = 0x8250;
+const DEBUG_TYPE_MARKER: c_uint = 0x8268;
+const DEBUG_TYPE_PUSH_GROUP: c_uint = 0x8269;
+const DEBUG_TYPE_POP_GROUP: c_uint = 0x826A;
+
+const DEBUG_SEVERITY_HIGH: c_uint = 0x9146;
+const DEBUG_SEVERITY_MEDIUM: c_uint = 0x9147;
+const DEBUG_SEVERITY_LOW: c_uint = 0x9148;
+const DEBUG_SEVERITY_NOTIFICATION: c_uint = 0x826B;
+
+extern "system" fn debug_callback(source: c_uint, ty: c_uint, id: c_uint, severity: c_uint,
+                                  length: c_int, message: *const c_char,
+                                  user_param: *const c_void)
+{
+    let output = unsafe { &*(user_param as *const DebugOutput) };
+
+    let message = unsafe {
+        if message.is_null() {
+            String::new()
+        } else if length < 0 {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        } else {
+            let bytes = slice::from_raw_parts(message as *const u8, length as usize);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    };
+
+    let msg = DebugMessage {
+        source: match source {
+            DEBUG_SOURCE_API => DebugSource::Api,
+            DEBUG_SOURCE_WINDOW_SYSTEM => DebugSource::WindowSystem,
+            DEBUG_SOURCE_SHADER_COMPILER => DebugSource::ShaderCompiler,
+            DEBUG_SOURCE_THIRD_PARTY => DebugSource::ThirdParty,
+            DEBUG_SOURCE_APPLICATION => DebugSource::Application,
+            _ => DebugSource::Other,
+        },
+        ty: match ty {
+            DEBUG_TYPE_ERROR => DebugType::Error,
+            DEBUG_TYPE_DEPRECATED_BEHAVIOR => DebugType::DeprecatedBehavior,
+            DEBUG_TYPE_UNDEFINED_BEHAVIOR => DebugType::UndefinedBehavior,
+            DEBUG_TYPE_PORTABILITY => DebugType::Portability,
+            DEBUG_TYPE_PERFORMANCE => DebugType::Performance,
+            DEBUG_TYPE_MARKER => DebugType::Marker,
+            DEBUG_TYPE_PUSH_GROUP => DebugType::PushGroup,
+            DEBUG_TYPE_POP_GROUP => DebugType::PopGroup,
+            _ => DebugType::Other,
+        },
+        id: id,
+        severity: match severity {
+            DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        },
+        message: message,
+    };
+
+    if !output.accepts(&msg) {
+        return;
+    }
+
+    // unwinding into the driver is undefined behavior
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| (output.callback)(&msg)));
+}
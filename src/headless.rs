@@ -1,14 +1,17 @@
 use Api;
 use ContextError;
 use CreationError;
+use DebugOutput;
 use GlAttributes;
 use GlProfile;
 use GlRequest;
 use GlContext;
 use PixelFormat;
 use PixelFormatRequirements;
+use ResetStatus;
 use Robustness;
 
+use debug::DebugOutputState;
 use platform;
 
 /// Object that allows you to build headless contexts.
@@ -53,6 +56,16 @@ impl<'a> HeadlessRendererBuilder<'a> {
         self
     }
 
+    /// Requests a forward-compatible OpenGL context, in which the deprecated features are
+    /// removed. Only applies to OpenGL 3.0 and later.
+    #[inline]
+    pub fn with_gl_forward_compatible(mut self, forward_compatible: bool)
+                                      -> HeadlessRendererBuilder<'a>
+    {
+        self.opengl.forward_compatible = forward_compatible;
+        self
+    }
+
     /// Sets the *debug* flag for the OpenGL context.
     ///
     /// The default value for this flag is `cfg!(ndebug)`, which means that it's enabled
@@ -63,6 +76,15 @@ impl<'a> HeadlessRendererBuilder<'a> {
         self
     }
 
+    /// Sets where the messages of the `GL_KHR_debug` extension are delivered.
+    ///
+    /// See `WindowBuilder::with_gl_debug_output`.
+    #[inline]
+    pub fn with_gl_debug_output(mut self, output: DebugOutput) -> HeadlessRendererBuilder<'a> {
+        self.opengl.debug_output = Some(output);
+        self
+    }
+
     /// Sets the robustness of the OpenGL context. See the docs of `Robustness`.
     #[inline]
     pub fn with_gl_robustness(mut self, robustness: Robustness) -> HeadlessRendererBuilder<'a> {
@@ -75,10 +97,20 @@ impl<'a> HeadlessRendererBuilder<'a> {
     /// Error should be very rare and only occur in case of permission denied, incompatible system,
     ///  out of memory, etc.
     #[inline]
-    pub fn build(self) -> Result<HeadlessContext, CreationError> {
-        platform::HeadlessContext::new(self.dimensions, &self.pf_reqs, &self.opengl,
-                                       &self.platform_specific)
-                .map(|w| HeadlessContext { context: w })
+    pub fn build(mut self) -> Result<HeadlessContext, CreationError> {
+        let context = try!(platform::HeadlessContext::new(self.dimensions, &self.pf_reqs,
+                                                          &self.opengl, &self.platform_specific));
+
+        let debug_output = if self.opengl.debug {
+            self.opengl.debug_output.take().map(DebugOutputState::new)
+        } else {
+            None
+        };
+
+        Ok(HeadlessContext {
+            context: context,
+            debug_output: debug_output,
+        })
     }
 
     /// Builds the headless context.
@@ -86,7 +118,8 @@ impl<'a> HeadlessRendererBuilder<'a> {
     /// The context is build in a *strict* way. That means that if the backend couldn't give
     /// you what you requested, an `Err` will be returned.
     #[inline]
-    pub fn build_strict(self) -> Result<HeadlessContext, CreationError> {
+    pub fn build_strict(mut self) -> Result<HeadlessContext, CreationError> {
+        self.opengl.strict = true;
         self.build()
     }
 }
@@ -94,6 +127,7 @@ impl<'a> HeadlessRendererBuilder<'a> {
 /// Represents a headless OpenGL context.
 pub struct HeadlessContext {
     context: platform::HeadlessContext,
+    debug_output: Option<DebugOutputState>,
 }
 
 impl HeadlessContext {
@@ -101,7 +135,13 @@ impl HeadlessContext {
     /// Sets the context as the current context.
     #[inline]
     pub unsafe fn make_current(&self) -> Result<(), ContextError> {
-        self.context.make_current()
+        try!(self.context.make_current());
+
+        if let Some(ref debug_output) = self.debug_output {
+            debug_output.install_if_needed(|addr| self.context.get_proc_address(addr));
+        }
+
+        Ok(())
     }
 
     /// Returns true if this context is the current one in this thread.
@@ -134,7 +174,7 @@ impl HeadlessContext {
 impl GlContext for HeadlessContext {
     #[inline]
     unsafe fn make_current(&self) -> Result<(), ContextError> {
-        self.context.make_current()
+        self.make_current()
     }
 
     #[inline]
@@ -152,11 +192,26 @@ impl GlContext for HeadlessContext {
         self.context.swap_buffers()
     }
 
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        self.context.resize_surface(width, height)
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.context.get_reset_status()
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         self.context.get_api()
     }
 
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.context.get_api_version()
+    }
+
     #[inline]
     fn get_pixel_format(&self) -> PixelFormat {
         self.context.get_pixel_format()
@@ -31,6 +31,9 @@ extern crate shared_library;
 
 extern crate libc;
 
+#[macro_use]
+extern crate log;
+
 extern crate winit;
 
 #[cfg(target_os = "windows")]
@@ -61,21 +64,32 @@ extern crate x11_dl;
 #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd"))]
 #[macro_use(wayland_env)]
 extern crate wayland_client;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd"))]
+#[macro_use]
+extern crate wayland_sys;
 
+pub use debug::{DebugOutput, DebugMessage, DebugSeverity, DebugSource, DebugType};
 pub use events::*;
 pub use headless::{HeadlessRendererBuilder, HeadlessContext};
 pub use window::{WindowProxy, PollEventsIterator, WaitEventsIterator};
 pub use window::{AvailableMonitorsIter, MonitorId, get_available_monitors, get_primary_monitor};
 pub use winit::NativeMonitorId;
 
+use std::cell::Cell;
 use std::io;
 #[cfg(not(target_os = "macos"))]
 use std::cmp::Ordering;
 
+#[macro_use]
+mod builder;
+
 mod api;
 mod platform;
+mod debug;
 mod events;
 mod headless;
+mod robustness;
+mod versions;
 mod window;
 
 pub mod os;
@@ -109,6 +123,9 @@ pub struct Window {
     // Только как? В том же линуксе platform::Window это перечисление. Блин, убейте меня, ничего ен понимаю.
     window: platform::Window,
     winit_window: winit::Window,
+    debug_output: Option<debug::DebugOutputState>,
+    // the buffer scale that was last given to the surface
+    buffer_scale: Cell<u32>,
 }
 
 /// Object that allows you to build windows.
@@ -150,9 +167,99 @@ pub trait GlContext {
     /// you can't know in advance whether `swap_buffers` will block or not.
     fn swap_buffers(&self) -> Result<(), ContextError>;
 
+    /// Swaps the buffers like `swap_buffers`, but tells the system that only the regions in
+    /// `rects` have changed since the previous frame.
+    ///
+    /// The system may use this information to only present or compose these regions. Backends
+    /// that don't support it swap the whole surface instead.
+    fn swap_buffers_with_damage(&self, _rects: &[Rect]) -> Result<(), ContextError> {
+        self.swap_buffers()
+    }
+
+    /// Tells the backend which regions of the back buffer are going to be modified during the
+    /// current frame. The contents outside of these regions become undefined after the next
+    /// swap.
+    ///
+    /// This must be called before any drawing command of the frame. It has no effect on backends
+    /// that don't support partial updates.
+    fn set_damage_region(&self, _rects: &[Rect]) -> Result<(), ContextError> {
+        Ok(())
+    }
+
+    /// Returns the age of the contents of the current back buffer, in frames.
+    ///
+    /// `Some(0)` means that the contents are undefined, `Some(1)` that the back buffer contains
+    /// the previous frame, `Some(2)` the frame before, and so on. Returns `None` if the backend
+    /// can't tell, in which case you should assume that the contents are undefined.
+    ///
+    /// The context must be current.
+    fn buffer_age(&self) -> Option<u32> {
+        None
+    }
+
+    /// Resizes the surface that the context renders to, in pixels.
+    ///
+    /// Most backends resize the surface along with the window, in which case this does nothing.
+    /// Wayland and OS X need to be told about the new size, and `Window` does this automatically
+    /// when the window is resized through `set_inner_size` or when its events iterators return
+    /// an `Event::Resized`.
+    fn resize_surface(&self, _width: u32, _height: u32) {
+    }
+
+    /// Returns the current values of the presentation counters of the surface.
+    ///
+    /// Returns `Err(ContextError::FunctionUnavailable)` if the backend doesn't support it.
+    fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        Err(ContextError::FunctionUnavailable)
+    }
+
+    /// Schedules a buffer swap for the first vertical retrace at which the media stream counter
+    /// (MSC) is greater than or equal to `target_msc`. If the MSC has already passed
+    /// `target_msc`, the swap happens at the next retrace where `msc % divisor == remainder`.
+    /// Passing `0` for all three values behaves like a regular `swap_buffers`.
+    ///
+    /// This function doesn't block. It returns the value that the swap buffer counter (SBC)
+    /// will have once the swap is completed, which can be passed to `wait_for_sbc`.
+    ///
+    /// Returns `Err(ContextError::FunctionUnavailable)` if the backend doesn't support it.
+    fn swap_buffers_msc(&self, _target_msc: i64, _divisor: i64, _remainder: i64)
+                        -> Result<i64, ContextError>
+    {
+        Err(ContextError::FunctionUnavailable)
+    }
+
+    /// Blocks until the swap buffer counter (SBC) of the surface reaches `target_sbc`, and
+    /// returns the counters at the time the swap was completed. A `target_sbc` of `0` waits for
+    /// all the pending swaps.
+    ///
+    /// Returns `Err(ContextError::FunctionUnavailable)` if the backend doesn't support it.
+    fn wait_for_sbc(&self, _target_sbc: i64) -> Result<SyncValues, ContextError> {
+        Err(ContextError::FunctionUnavailable)
+    }
+
+    /// Returns whether the context has been reset, for example after a GPU fault, and why.
+    ///
+    /// This is only meaningful for contexts created with `RobustLoseContextOnReset` or
+    /// `TryRobustLoseContextOnReset`, and the driver is only queried if the context is current.
+    /// Once a reset has been observed, it is always reported: the context must be recreated.
+    /// On these contexts `swap_buffers` also returns `ContextError::ContextLost` after a reset.
+    fn get_reset_status(&self) -> ResetStatus {
+        ResetStatus::NoError
+    }
+
     /// Returns the OpenGL API being used.
     fn get_api(&self) -> Api;
 
+    /// Returns the version of the API that was requested when the context was created, or
+    /// `None` if it is unknown.
+    ///
+    /// With `GlRequest::Latest`, this is the highest version that the implementation accepted.
+    /// The implementation is allowed to return a more recent but compatible version, which can
+    /// be queried with `glGetString(GL_VERSION)`.
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        None
+    }
+
     /// Returns the pixel format of the main framebuffer of the context.
     fn get_pixel_format(&self) -> PixelFormat;
 }
@@ -167,9 +274,46 @@ pub enum CreationError {
     RobustnessNotSupported,
     OpenGlVersionNotSupported,
     NoAvailablePixelFormat,
+    /// A function of the backend failed.
+    BackendError {
+        backend: Backend,
+        /// The native error code, for example the value returned by `eglGetError`.
+        code: Option<i32>,
+        message: String,
+    },
+    /// Every attempt at creating a context failed. The attempts are listed in the order in
+    /// which they were made.
+    AttemptsFailed(Vec<CreationAttempt>),
 }
 
 impl CreationError {
+    /// Turns the error into a list of attempts made with `backend`.
+    ///
+    /// The attempts of an `AttemptsFailed` error are returned as they are, with their `library`
+    /// filled if it was unknown. Any other error is turned into a single attempt.
+    fn into_attempts(self, backend: Backend, library: Option<&str>,
+                     profile: Option<GlProfile>) -> Vec<CreationAttempt>
+    {
+        match self {
+            CreationError::AttemptsFailed(mut attempts) => {
+                for attempt in attempts.iter_mut() {
+                    if attempt.library.is_none() {
+                        attempt.library = library.map(|l| l.to_owned());
+                    }
+                }
+                attempts
+            },
+            err => vec![CreationAttempt {
+                backend: backend,
+                library: library.map(|l| l.to_owned()),
+                version: None,
+                profile: profile,
+                config_id: None,
+                error: err,
+            }],
+        }
+    }
+
     fn to_string(&self) -> &str {
         match *self {
             CreationError::OsError(ref text) => &text,
@@ -181,13 +325,23 @@ impl CreationError {
                                                          supported.",
             CreationError::NoAvailablePixelFormat => "Couldn't find any pixel format that matches \
                                                       the criterias.",
+            CreationError::BackendError { ref message, .. } => &message,
+            CreationError::AttemptsFailed(_) => "Every attempt at creating a context failed",
         }
     }
 }
 
 impl std::fmt::Display for CreationError {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        formatter.write_str(self.to_string())
+        try!(formatter.write_str(self.to_string()));
+
+        if let CreationError::AttemptsFailed(ref attempts) = *self {
+            for attempt in attempts {
+                try!(write!(formatter, "\n  - {}", attempt));
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -199,16 +353,63 @@ impl std::error::Error for CreationError {
     fn cause(&self) -> Option<&std::error::Error> {
         match *self {
             CreationError::NoBackendAvailable(ref err) => Some(&**err),
+            CreationError::AttemptsFailed(ref attempts) => {
+                attempts.last().map(|a| &a.error as &std::error::Error)
+            },
             _ => None
         }
     }
 }
 
+/// A failed attempt at creating a context. See `CreationError::AttemptsFailed`.
+#[derive(Debug)]
+pub struct CreationAttempt {
+    pub backend: Backend,
+    /// The file name of the library that was used, if known.
+    pub library: Option<String>,
+    /// The API and version that were requested, or `None` if the attempt failed before a
+    /// version was requested.
+    pub version: Option<(Api, (u8, u8))>,
+    pub profile: Option<GlProfile>,
+    /// The identifier of the `GLXFBConfig` or `EGLConfig` that was used, if any.
+    pub config_id: Option<i32>,
+    /// Why the attempt failed. Errors returned by the backend carry their native error code.
+    pub error: CreationError,
+}
+
+impl std::fmt::Display for CreationAttempt {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        try!(write!(formatter, "{:?}", self.backend));
+        if let Some(ref library) = self.library {
+            try!(write!(formatter, " ({})", library));
+        }
+        if let Some((api, (major, minor))) = self.version {
+            try!(write!(formatter, ", {:?} {}.{}", api, major, minor));
+        }
+        if let Some(profile) = self.profile {
+            try!(write!(formatter, ", {:?} profile", profile));
+        }
+        if let Some(config_id) = self.config_id {
+            try!(write!(formatter, ", config 0x{:x}", config_id));
+        }
+        write!(formatter, ": {}", self.error)
+    }
+}
+
 /// Error that can happen when manipulating an OpenGL context.
 #[derive(Debug)]
 pub enum ContextError {
     IoError(io::Error),
     ContextLost,
+    /// The function isn't supported by the backend or the driver.
+    FunctionUnavailable,
+    /// A function of the backend failed.
+    BackendError {
+        backend: Backend,
+        /// The native error code, for example the value returned by `eglGetError`.
+        code: Option<i32>,
+        message: String,
+    },
 }
 
 impl ContextError {
@@ -216,7 +417,10 @@ impl ContextError {
         use std::error::Error;
         match *self {
             ContextError::IoError(ref err) => err.description(),
-            ContextError::ContextLost => "Context lost"
+            ContextError::ContextLost => "Context lost",
+            ContextError::FunctionUnavailable => "This function is not supported by the backend \
+                                                  or the driver",
+            ContextError::BackendError { ref message, .. } => &message,
         }
     }
 }
@@ -233,6 +437,17 @@ impl std::error::Error for ContextError {
     }
 }
 
+/// The library that reported an error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Egl,
+    Glx,
+    Wgl,
+    OsMesa,
+    X11,
+    Winit,
+}
+
 /// All APIs related to OpenGL that you can possibly get while using glutin.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Api {
@@ -275,14 +490,32 @@ pub enum GlRequest {
         /// The version to use for OpenGL ES.
         opengles_version: (u8, u8),
     },
+
+    /// Request the newest available version of a specific API, as long as it is at least the
+    /// specified version.
+    ///
+    /// Example: `GlRequest::AtLeast(Api::OpenGl, (3, 3))`.
+    AtLeast(Api, (u8, u8)),
+
+    /// Like `GlThenGles`, but requests the newest available version of OpenGL or OpenGL ES, as
+    /// long as it is at least `opengl_version` or `opengles_version`.
+    AtLeastGlThenGles {
+        /// The minimum version to use for OpenGL.
+        opengl_version: (u8, u8),
+        /// The minimum version to use for OpenGL ES.
+        opengles_version: (u8, u8),
+    },
 }
 
 impl GlRequest {
-    /// Extract the desktop GL version, if any.
+    /// Extract the desktop GL version, if any. For the `AtLeast` requests, this is the minimum
+    /// version.
     pub fn to_gl_version(&self) -> Option<(u8, u8)> {
         match self {
             &GlRequest::Specific(Api::OpenGl, version) => Some(version),
             &GlRequest::GlThenGles { opengl_version: version, .. } => Some(version),
+            &GlRequest::AtLeast(Api::OpenGl, version) => Some(version),
+            &GlRequest::AtLeastGlThenGles { opengl_version: version, .. } => Some(version),
             _ => None,
         }
     }
@@ -327,6 +560,22 @@ pub enum Robustness {
     TryRobustLoseContextOnReset,
 }
 
+/// Whether a robust context has been reset. See `GlContext::get_reset_status`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResetStatus {
+    /// The context hasn't been reset.
+    NoError,
+
+    /// The context has been reset because of a fault that it caused.
+    Guilty,
+
+    /// The context has been reset because of a fault caused by another context.
+    Innocent,
+
+    /// The context has been reset, but the cause is unknown.
+    Unknown,
+}
+
 /// The behavior of the driver when you change the current context.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ReleaseBehavior {
@@ -356,6 +605,30 @@ pub struct PixelFormat {
     pub srgb: bool,
 }
 
+/// A rectangular region of a surface, in pixels.
+///
+/// Like in OpenGL, the origin is the bottom-left hand corner of the surface.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The presentation counters of a surface, as reported by the system.
+///
+/// See `GlContext::get_sync_values`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SyncValues {
+    /// Unadjusted system time, in microseconds, at which `msc` was last incremented.
+    pub ust: i64,
+    /// Media stream counter. Incremented at each vertical retrace.
+    pub msc: i64,
+    /// Swap buffer counter. Incremented each time a buffer swap is completed.
+    pub sbc: i64,
+}
+
 /// Describes how the backend should choose a pixel format.
 // TODO: swap method? (swap, copy)
 #[derive(Clone, Debug)]
@@ -445,6 +718,12 @@ pub struct GlAttributes<S> {
     /// The default is `None`.
     pub profile: Option<GlProfile>,
 
+    /// Whether to create a forward-compatible context, in which the features that are marked as
+    /// deprecated by the requested version are removed. Only applies to OpenGL 3.0 and later.
+    ///
+    /// The default is `false`.
+    pub forward_compatible: bool,
+
     /// Whether to enable the `debug` flag of the context.
     ///
     /// Debug contexts are usually slower but give better error reporting.
@@ -452,6 +731,12 @@ pub struct GlAttributes<S> {
     /// The default is `true` in debug mode and `false` in release mode.
     pub debug: bool,
 
+    /// Where to deliver the messages of the `GL_KHR_debug` extension. Only used if `debug` is
+    /// `true`.
+    ///
+    /// The default is `None`.
+    pub debug_output: Option<DebugOutput>,
+
     /// How the OpenGL context should detect errors.
     ///
     /// The default is `NotRobust` because this is what is typically expected when you create an
@@ -463,6 +748,12 @@ pub struct GlAttributes<S> {
     ///
     /// The default is `false`.
     pub vsync: bool,
+
+    /// Whether the backend should return an error instead of silently ignoring the attributes
+    /// it couldn't honor, for example a vsync request that the driver refused.
+    ///
+    /// The default is `false`. It is set by `build_strict`.
+    pub strict: bool,
 }
 
 impl<S> GlAttributes<S> {
@@ -473,9 +764,29 @@ impl<S> GlAttributes<S> {
             sharing: self.sharing.map(f),
             version: self.version,
             profile: self.profile,
+            forward_compatible: self.forward_compatible,
+            debug: self.debug,
+            debug_output: self.debug_output,
+            robustness: self.robustness,
+            vsync: self.vsync,
+            strict: self.strict,
+        }
+    }
+
+    /// Returns a copy of the attributes that doesn't share lists with any context, whatever the
+    /// type of `sharing`.
+    #[inline]
+    pub fn without_sharing<T>(&self) -> GlAttributes<T> {
+        GlAttributes {
+            sharing: None,
+            version: self.version,
+            profile: self.profile,
+            forward_compatible: self.forward_compatible,
             debug: self.debug,
+            debug_output: self.debug_output.clone(),
             robustness: self.robustness,
             vsync: self.vsync,
+            strict: self.strict,
         }
     }
 }
@@ -487,9 +798,12 @@ impl<S> Default for GlAttributes<S> {
             sharing: None,
             version: GlRequest::Latest,
             profile: None,
+            forward_compatible: false,
             debug: cfg!(debug_assertions),
+            debug_output: None,
             robustness: Robustness::NotRobust,
             vsync: false,
+            strict: false,
         }
     }
 }
@@ -1 +1,543 @@
 #![cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+
+use std::os::raw::{c_ulong, c_void};
+
+use Api;
+use ContextError;
+use CreationError;
+use GlAttributes;
+use GlContext;
+use GlRequest;
+use PixelFormat;
+use PixelFormatRequirements;
+use Rect;
+use ResetStatus;
+use SyncValues;
+use Window;
+use WindowBuilder;
+
+use api::wayland;
+use debug::DebugOutputState;
+use platform;
+
+/// The backend that is used to create OpenGL contexts on X11.
+///
+/// Can be overridden with the `GLUTIN_X11_BACKEND` environment variable, whose value is
+/// `glx`, `egl`, `glx-only` or `egl-only`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum X11Backend {
+    /// Try GLX first, then EGL if it fails.
+    PreferGlx,
+    /// Try EGL first, then GLX if it fails.
+    PreferEgl,
+    /// Only use GLX.
+    GlxOnly,
+    /// Only use EGL.
+    EglOnly,
+}
+
+/// The display server that a window is connected to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnixBackend {
+    /// An X server, which may be XWayland.
+    X11,
+    /// A Wayland compositor.
+    Wayland,
+}
+
+/// Additional methods on `Window` that are specific to Unix systems.
+pub trait WindowExt {
+    /// Returns the path of the GLX or EGL library that was loaded to create the context.
+    ///
+    /// This is the path that was passed to `dlopen`, which can be just a file name.
+    fn get_gl_library_path(&self) -> &str;
+
+    /// Returns the display server that the window is connected to.
+    ///
+    /// winit connects to a Wayland compositor when one is running, unless the
+    /// `WINIT_UNIX_BACKEND` environment variable is set to `x11` when the program starts.
+    fn get_unix_backend(&self) -> UnixBackend;
+
+    /// Asks the Wayland compositor to signal when it wants a new frame to be drawn, through a
+    /// `wl_surface.frame` callback.
+    ///
+    /// The request is sent along with the next `swap_buffers`, and the frame is then signaled by
+    /// `poll_frame` or `wait_frame`. The compositor doesn't signal frames for hidden surfaces, so
+    /// rendering only when a frame was signaled avoids wasting CPU while the window is minimized.
+    ///
+    /// As the frames are then paced by the compositor, the first call disables the vsync of the
+    /// context so that `swap_buffers` doesn't wait for a frame on its own.
+    ///
+    /// Returns `false` if the window doesn't use Wayland, in which case frames are never
+    /// signaled.
+    fn request_frame(&self) -> bool;
+
+    /// Returns the timestamp in milliseconds given by the compositor if the frame that was
+    /// requested with `request_frame` was signaled since the last call, or `None` otherwise.
+    ///
+    /// The frame callbacks are received when the events of the window are polled, so this
+    /// should be called after `poll_events` or `wait_events`.
+    fn poll_frame(&self) -> Option<u32>;
+
+    /// Blocks until the frame that was requested with `request_frame` is signaled, and returns
+    /// the timestamp in milliseconds given by the compositor.
+    ///
+    /// The events of the window that arrive in the meantime are kept for `poll_events`. Returns
+    /// `None` right away if no frame was requested or if the window doesn't use Wayland.
+    fn wait_frame(&self) -> Option<u32>;
+
+    /// Creates a Wayland subsurface of `width` and `height` pixels attached to the window, with
+    /// an OpenGL context of its own whose config meets `pf_reqs`.
+    ///
+    /// The subsurface is shown on top of the window at its top-left corner, and is composited
+    /// by the compositor, which lets you present for example a video in a layer separate from the
+    /// rest of the UI. Overlays that let the window show through need a pixel format with
+    /// alpha bits. The context uses the same API and version as the one of the window, and
+    /// doesn't share lists with it.
+    ///
+    /// Returns `CreationError::NotSupported` if the window doesn't use Wayland or if the
+    /// compositor doesn't support subsurfaces.
+    fn create_subsurface(&self, width: u32, height: u32, pf_reqs: &PixelFormatRequirements)
+                         -> Result<Subsurface, CreationError>;
+
+    /// Makes the buffers that the context renders to `size` pixels big, whatever the size of
+    /// the window, and has the Wayland compositor scale them to the window through
+    /// `wp_viewporter`. Pass `None` to have them follow the size of the window again.
+    ///
+    /// This lets you render at a lower resolution to keep up the frame rate. While a size is
+    /// set, the drawable no longer matches `get_inner_size_pixels`, so pass `size` to
+    /// `glViewport` instead.
+    ///
+    /// Returns `false` if the window doesn't use Wayland or if the compositor doesn't support
+    /// `wp_viewporter`, in which case nothing changes.
+    fn set_buffer_size(&self, size: Option<(u32, u32)>) -> bool;
+}
+
+impl WindowExt for Window {
+    #[inline]
+    fn get_gl_library_path(&self) -> &str {
+        self.window.get_library_path()
+    }
+
+    #[inline]
+    fn get_unix_backend(&self) -> UnixBackend {
+        self.window.get_unix_backend()
+    }
+
+    #[inline]
+    fn request_frame(&self) -> bool {
+        self.window.request_frame()
+    }
+
+    #[inline]
+    fn poll_frame(&self) -> Option<u32> {
+        self.window.poll_frame()
+    }
+
+    #[inline]
+    fn wait_frame(&self) -> Option<u32> {
+        self.window.wait_frame()
+    }
+
+    fn create_subsurface(&self, width: u32, height: u32, pf_reqs: &PixelFormatRequirements)
+                         -> Result<Subsurface, CreationError>
+    {
+        let mut opengl: GlAttributes<()> = Default::default();
+        if let Some(version) = self.window.get_api_version() {
+            opengl.version = GlRequest::Specific(self.window.get_api(), version);
+        }
+
+        let subsurface = try!(self.window.create_subsurface(&self.winit_window, (width, height),
+                                                            pf_reqs, &opengl));
+        Ok(Subsurface { subsurface: subsurface })
+    }
+
+    #[inline]
+    fn set_buffer_size(&self, size: Option<(u32, u32)>) -> bool {
+        self.window.set_buffer_size(size)
+    }
+}
+
+/// An OpenGL context rendering to a Wayland subsurface of a `Window`.
+///
+/// See `WindowExt::create_subsurface`. The position, the stacking order and the mode of the
+/// subsurface take effect when the next buffer of the window is swapped.
+pub struct Subsurface {
+    subsurface: wayland::Subsurface,
+}
+
+impl Subsurface {
+    /// Moves the subsurface relative to the top-left corner of the window, in points.
+    #[inline]
+    pub fn set_position(&self, x: i32, y: i32) {
+        self.subsurface.set_position(x, y)
+    }
+
+    /// Puts the subsurface right above another subsurface of the same window.
+    #[inline]
+    pub fn place_above(&self, sibling: &Subsurface) {
+        self.subsurface.place_above(sibling.subsurface.get_surface())
+    }
+
+    /// Puts the subsurface right below another subsurface of the same window.
+    #[inline]
+    pub fn place_below(&self, sibling: &Subsurface) {
+        self.subsurface.place_below(sibling.subsurface.get_surface())
+    }
+
+    /// Puts the subsurface right above or right below the window it is attached to.
+    ///
+    /// `window` must be the window that created the subsurface.
+    #[inline]
+    pub fn place_relative_to_window(&self, window: &Window, above: bool) {
+        if let platform::Window::Wayland(ref w) = window.window {
+            if above {
+                self.subsurface.place_above(w.get_surface())
+            } else {
+                self.subsurface.place_below(w.get_surface())
+            }
+        }
+    }
+
+    /// Sets the factor between the size of the buffers in pixels and the size of the subsurface
+    /// in points, for HiDPI outputs. A scale of 0 is treated as 1.
+    #[inline]
+    pub fn set_buffer_scale(&self, scale: u32) {
+        self.subsurface.set_buffer_scale(scale)
+    }
+
+    /// Chooses between the synchronized mode, which is the default and where the buffers swapped
+    /// on the subsurface are only shown along with the window's next buffer, and the
+    /// desynchronized mode where they are shown right away.
+    #[inline]
+    pub fn set_sync(&self, sync: bool) {
+        self.subsurface.set_sync(sync)
+    }
+}
+
+impl GlContext for Subsurface {
+    #[inline]
+    unsafe fn make_current(&self) -> Result<(), ContextError> {
+        self.subsurface.make_current()
+    }
+
+    #[inline]
+    fn is_current(&self) -> bool {
+        self.subsurface.is_current()
+    }
+
+    #[inline]
+    fn get_proc_address(&self, addr: &str) -> *const () {
+        self.subsurface.get_proc_address(addr)
+    }
+
+    #[inline]
+    fn swap_buffers(&self) -> Result<(), ContextError> {
+        self.subsurface.swap_buffers()
+    }
+
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.subsurface.swap_buffers_with_damage(rects)
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.subsurface.set_damage_region(rects)
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        self.subsurface.buffer_age()
+    }
+
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        self.subsurface.resize_surface(width, height)
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.subsurface.get_reset_status()
+    }
+
+    #[inline]
+    fn get_api(&self) -> Api {
+        self.subsurface.get_api()
+    }
+
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.subsurface.get_api_version()
+    }
+
+    #[inline]
+    fn get_pixel_format(&self) -> PixelFormat {
+        self.subsurface.get_pixel_format()
+    }
+}
+
+/// Additional methods on `WindowBuilder` that are specific to Unix systems.
+///
+/// The GLX and EGL libraries are searched in this order: the paths of the `GLUTIN_GLX_LIBRARY`
+/// and `GLUTIN_EGL_LIBRARY` environment variables (separated with `:`), the paths given to
+/// this builder, and finally the system libraries. The vendor-neutral `libGLX.so.0` of GLVND is
+/// preferred over `libGL.so.1`.
+pub trait WindowBuilderExt<'a> {
+    /// Adds a GLX library to try before the system ones.
+    ///
+    /// Can be called multiple times. The libraries are tried in the order they were added.
+    fn with_glx_library<P: Into<String>>(self, path: P) -> WindowBuilder<'a>;
+
+    /// Adds an EGL library to try before the system ones.
+    ///
+    /// Can be called multiple times. The libraries are tried in the order they were added.
+    fn with_egl_library<P: Into<String>>(self, path: P) -> WindowBuilder<'a>;
+
+    /// Sets the backend that is used to create the context on X11.
+    ///
+    /// By default GLX is tried first for desktop OpenGL and EGL first for OpenGL ES, and the
+    /// other backend is tried if the first one fails.
+    fn with_x11_backend(self, backend: X11Backend) -> WindowBuilder<'a>;
+}
+
+impl<'a> WindowBuilderExt<'a> for WindowBuilder<'a> {
+    #[inline]
+    fn with_glx_library<P: Into<String>>(mut self, path: P) -> WindowBuilder<'a> {
+        self.platform_specific.glx_libraries.push(path.into());
+        self
+    }
+
+    #[inline]
+    fn with_egl_library<P: Into<String>>(mut self, path: P) -> WindowBuilder<'a> {
+        self.platform_specific.egl_libraries.push(path.into());
+        self
+    }
+
+    #[inline]
+    fn with_x11_backend(mut self, backend: X11Backend) -> WindowBuilder<'a> {
+        self.platform_specific.x11_backend = Some(backend);
+        self
+    }
+}
+
+/// Object that allows you to build contexts for native windows that weren't created by winit,
+/// like the ones that GTK or Qt give to plugins.
+pub struct RawContextBuilder {
+    pf_reqs: PixelFormatRequirements,
+    // raw contexts can't share lists, as there is no raw window to share them with
+    opengl: GlAttributes<()>,
+    platform_specific: platform::PlatformSpecificWindowBuilderAttributes,
+}
+
+impl RawContextBuilder {
+    /// Initializes a new `RawContextBuilder` with default values.
+    #[inline]
+    pub fn new() -> RawContextBuilder {
+        RawContextBuilder {
+            pf_reqs: Default::default(),
+            opengl: Default::default(),
+            platform_specific: Default::default(),
+        }
+    }
+
+    context_builder_setters!(RawContextBuilder);
+
+    /// Builds the context in a *strict* way, like `WindowBuilder::build_strict`. That means
+    /// that if the backend couldn't give you what you requested, an `Err` will be returned.
+    #[inline]
+    pub fn strict(mut self) -> RawContextBuilder {
+        self.opengl.strict = true;
+        self
+    }
+
+    /// Adds a GLX library to try before the system ones. See `WindowBuilderExt`.
+    #[inline]
+    pub fn with_glx_library<P: Into<String>>(mut self, path: P) -> RawContextBuilder {
+        self.platform_specific.glx_libraries.push(path.into());
+        self
+    }
+
+    /// Adds an EGL library to try before the system ones. See `WindowBuilderExt`.
+    #[inline]
+    pub fn with_egl_library<P: Into<String>>(mut self, path: P) -> RawContextBuilder {
+        self.platform_specific.egl_libraries.push(path.into());
+        self
+    }
+
+    /// Sets the backend that is used to create the context on X11. See `WindowBuilderExt`.
+    #[inline]
+    pub fn with_x11_backend(mut self, backend: X11Backend) -> RawContextBuilder {
+        self.platform_specific.x11_backend = Some(backend);
+        self
+    }
+
+    /// Builds a context that renders to `window`, a window of the Xlib `Display` `display`.
+    ///
+    /// The context is created on `display`, whether winit uses X11 or Wayland, and its config is
+    /// chosen among the ones whose visual is the visual of the window. The display is never
+    /// closed by glutin. While the context is created, the X errors of `display` are caught by an
+    /// error handler of glutin, and your handler is restored afterwards. As Xlib has a single
+    /// error handler, contexts built by several threads are created one after the other.
+    ///
+    /// # Unsafety
+    ///
+    /// `display` and `window` must be valid, and must outlive the context. `XInitThreads` must
+    /// have been called if the display is used from several threads.
+    #[inline]
+    pub unsafe fn build_x11(self, display: *mut c_void, window: c_ulong)
+                            -> Result<RawContext, CreationError>
+    {
+        let context = try!(platform::Window::new_x11_raw(display, window, &self.pf_reqs,
+                                                         &self.opengl, &self.platform_specific));
+        Ok(RawContext::new(context, self.opengl))
+    }
+
+    /// Builds a context that renders to `surface`, a `wl_surface` of the `wl_display` `display`,
+    /// with buffers of `width` and `height` pixels.
+    ///
+    /// Only the buffers of the surface are managed by the context. Its role, its input and the
+    /// rest of its state stay under your control. The buffer scale is 1 until you call
+    /// `set_buffer_scale`, and `resize_surface` must be called whenever the size of the surface
+    /// changes.
+    ///
+    /// # Unsafety
+    ///
+    /// `display` and `surface` must be valid, and must outlive the context.
+    #[inline]
+    pub unsafe fn build_wayland(self, display: *mut c_void, surface: *mut c_void, width: u32,
+                                height: u32) -> Result<RawContext, CreationError>
+    {
+        let context = try!(platform::Window::new_wayland_raw(display, surface, (width, height),
+                                                             &self.pf_reqs, &self.opengl,
+                                                             &self.platform_specific));
+        Ok(RawContext::new(context, self.opengl))
+    }
+}
+
+/// An OpenGL context rendering to a native window that wasn't created by winit.
+///
+/// See `RawContextBuilder`.
+pub struct RawContext {
+    context: platform::Window,
+    debug_output: Option<DebugOutputState>,
+}
+
+impl RawContext {
+    fn new(context: platform::Window, opengl: GlAttributes<()>) -> RawContext {
+        let debug_output = if opengl.debug {
+            opengl.debug_output.map(DebugOutputState::new)
+        } else {
+            None
+        };
+
+        RawContext {
+            context: context,
+            debug_output: debug_output,
+        }
+    }
+
+    /// Returns the display server that the context renders to.
+    #[inline]
+    pub fn get_unix_backend(&self) -> UnixBackend {
+        self.context.get_unix_backend()
+    }
+
+    /// Returns the path of the GLX or EGL library that was loaded to create the context.
+    #[inline]
+    pub fn get_gl_library_path(&self) -> &str {
+        self.context.get_library_path()
+    }
+
+    /// Sets the factor between the size of the buffers in pixels and the size of the Wayland
+    /// surface in points, for HiDPI outputs. A scale of 0 is treated as 1. Does nothing on X11.
+    #[inline]
+    pub fn set_buffer_scale(&self, scale: u32) {
+        self.context.set_buffer_scale(scale)
+    }
+}
+
+impl GlContext for RawContext {
+    #[inline]
+    unsafe fn make_current(&self) -> Result<(), ContextError> {
+        try!(self.context.make_current());
+
+        if let Some(ref debug_output) = self.debug_output {
+            debug_output.install_if_needed(|addr| self.context.get_proc_address(addr));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    #[inline]
+    fn get_proc_address(&self, addr: &str) -> *const () {
+        self.context.get_proc_address(addr)
+    }
+
+    #[inline]
+    fn swap_buffers(&self) -> Result<(), ContextError> {
+        self.context.swap_buffers()
+    }
+
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.context.swap_buffers_with_damage(rects)
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.context.set_damage_region(rects)
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        self.context.buffer_age()
+    }
+
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        self.context.resize_surface(width, height)
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.context.get_reset_status()
+    }
+
+    #[inline]
+    fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        self.context.get_sync_values()
+    }
+
+    #[inline]
+    fn swap_buffers_msc(&self, target_msc: i64, divisor: i64, remainder: i64)
+                        -> Result<i64, ContextError>
+    {
+        self.context.swap_buffers_msc(target_msc, divisor, remainder)
+    }
+
+    #[inline]
+    fn wait_for_sbc(&self, target_sbc: i64) -> Result<SyncValues, ContextError> {
+        self.context.wait_for_sbc(target_sbc)
+    }
+
+    #[inline]
+    fn get_api(&self) -> Api {
+        self.context.get_api()
+    }
+
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.context.get_api_version()
+    }
+
+    #[inline]
+    fn get_pixel_format(&self) -> PixelFormat {
+        self.context.get_pixel_format()
+    }
+}
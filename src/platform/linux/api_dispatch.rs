@@ -1,3 +1,4 @@
+use std::os::raw::c_void;
 use winit;
 
 use ContextError;
@@ -6,15 +7,27 @@ use GlAttributes;
 use GlContext;
 use PixelFormat;
 use PixelFormatRequirements;
+use Rect;
+use ResetStatus;
+use SyncValues;
 use WindowAttributes;
 
+use os::unix::{UnixBackend, X11Backend};
+
 use api::wayland;
 use api::x11;
 
 use winit::os::unix::WindowExt;
 
 #[derive(Clone, Default)]
-pub struct PlatformSpecificWindowBuilderAttributes;
+pub struct PlatformSpecificWindowBuilderAttributes {
+    /// Paths of the GLX libraries to try before the default ones.
+    pub glx_libraries: Vec<String>,
+    /// Paths of the EGL libraries to try before the default ones.
+    pub egl_libraries: Vec<String>,
+    /// The backend to use on X11, or `None` to choose depending on the requested API.
+    pub x11_backend: Option<X11Backend>,
+}
 
 pub enum Window {
     #[doc(hidden)]
@@ -26,35 +39,150 @@ pub enum Window {
 pub use winit::platform::{MonitorId, get_available_monitors, get_primary_monitor};
 
 impl Window {
+    /// Creates a context for `winit_window`, or for a new window if it is `None`, and returns it
+    /// along with the window.
     #[inline]
     pub fn new(
-        _: &WindowAttributes, // вот это надо бы убрать
+        window: &WindowAttributes,
         pf_reqs: &PixelFormatRequirements,
         opengl: &GlAttributes<&Window>,
-        _: &PlatformSpecificWindowBuilderAttributes, // и это, наверное, тоже убрать
-        winit_window: &winit::Window,
-    ) -> Result<Window, CreationError> {
-        let is_x11 = winit_window.get_xlib_display().is_some();
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+        winit_window: Option<winit::Window>,
+    ) -> Result<(Window, winit::Window), CreationError> {
+        let is_x11 = match winit_window {
+            Some(ref w) => w.get_xlib_display().is_some(),
+            None => winit::os::unix::get_x11_xconnection().is_some(),
+        };
+        info!("Creating the window on {}", if is_x11 { "X11" } else { "Wayland" });
         if is_x11 {
             let opengl = opengl.clone().map_sharing(|w| match w {
                 &Window::X(ref w) => w,
                 _ => panic!()       // TODO: return an error
             });
             x11::Window::new(
+                window,
                 pf_reqs,
                 &opengl,
+                pl_attribs,
                 winit_window,
-            ).map(Window::X)
+            ).map(|(w, winit_window)| (Window::X(w), winit_window))
         } else {
             let opengl = opengl.clone().map_sharing(|w| match w {
                 &Window::Wayland(ref w) => w,
                 _ => panic!()       // TODO: return an error
             });
             wayland::Window::new(
+                window,
                 pf_reqs,
                 &opengl,
+                pl_attribs,
                 winit_window,
-            ).map(Window::Wayland)
+            ).map(|(w, winit_window)| (Window::Wayland(w), winit_window))
+        }
+    }
+
+    /// Creates a context for an X window that wasn't created by winit.
+    #[inline]
+    pub unsafe fn new_x11_raw(
+        display: *mut c_void,
+        window: x11::ffi::Window,
+        pf_reqs: &PixelFormatRequirements,
+        opengl: &GlAttributes<()>,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+    ) -> Result<Window, CreationError> {
+        x11::Window::new_raw(display as *mut _, window, pf_reqs, &opengl.without_sharing(),
+                             pl_attribs)
+            .map(Window::X)
+    }
+
+    /// Creates a context for a Wayland surface that wasn't created by winit.
+    #[inline]
+    pub unsafe fn new_wayland_raw(
+        display: *mut c_void,
+        surface: *mut c_void,
+        dimensions: (u32, u32),
+        pf_reqs: &PixelFormatRequirements,
+        opengl: &GlAttributes<()>,
+        pl_attribs: &PlatformSpecificWindowBuilderAttributes,
+    ) -> Result<Window, CreationError> {
+        wayland::Window::new_raw(display, surface, dimensions, pf_reqs,
+                                 &opengl.without_sharing(), pl_attribs)
+            .map(Window::Wayland)
+    }
+
+    /// Sets the buffer scale of the surface on Wayland. Does nothing on X11.
+    #[inline]
+    pub fn set_buffer_scale(&self, scale: u32) {
+        match self {
+            &Window::X(_) => (),
+            &Window::Wayland(ref w) => w.set_buffer_scale(scale),
+        }
+    }
+
+    /// Asks the compositor for a frame callback. Returns `false` if the window doesn't use
+    /// Wayland, in which case no frame will ever be signaled.
+    #[inline]
+    pub fn request_frame(&self) -> bool {
+        match self {
+            &Window::X(_) => false,
+            &Window::Wayland(ref w) => { w.request_frame(); true },
+        }
+    }
+
+    #[inline]
+    pub fn poll_frame(&self) -> Option<u32> {
+        match self {
+            &Window::X(_) => None,
+            &Window::Wayland(ref w) => w.poll_frame(),
+        }
+    }
+
+    #[inline]
+    pub fn wait_frame(&self) -> Option<u32> {
+        match self {
+            &Window::X(_) => None,
+            &Window::Wayland(ref w) => w.wait_frame(),
+        }
+    }
+
+    /// Creates a Wayland subsurface attached to the window. Returns `NotSupported` on X11.
+    pub fn create_subsurface(&self, winit_window: &winit::Window, dimensions: (u32, u32),
+                             pf_reqs: &PixelFormatRequirements,
+                             opengl: &GlAttributes<()>)
+                             -> Result<wayland::Subsurface, CreationError>
+    {
+        match self {
+            &Window::X(_) => Err(CreationError::NotSupported),
+            &Window::Wayland(ref w) => {
+                w.create_subsurface(winit_window, dimensions, pf_reqs, &opengl.without_sharing())
+            },
+        }
+    }
+
+    /// Decouples the size of the buffers from the size of the window. Returns `false` on X11.
+    #[inline]
+    pub fn set_buffer_size(&self, size: Option<(u32, u32)>) -> bool {
+        match self {
+            &Window::X(_) => false,
+            &Window::Wayland(ref w) => w.set_buffer_size(size),
+        }
+    }
+
+    /// Returns the display server that the window is connected to.
+    #[inline]
+    pub fn get_unix_backend(&self) -> UnixBackend {
+        match self {
+            &Window::X(_) => UnixBackend::X11,
+            &Window::Wayland(_) => UnixBackend::Wayland,
+        }
+    }
+
+    /// Returns the path of the GLX or EGL library that was loaded to create the context.
+    #[inline]
+    pub fn get_library_path(&self) -> &str {
+        match self {
+            &Window::X(ref w) => w.get_library_path(),
+            &Window::Wayland(ref w) => w.get_library_path()
         }
     }
 }
@@ -92,6 +220,72 @@ impl GlContext for Window {
         }
     }
 
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        match self {
+            &Window::X(ref w) => w.swap_buffers_with_damage(rects),
+            &Window::Wayland(ref w) => w.swap_buffers_with_damage(rects)
+        }
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        match self {
+            &Window::X(ref w) => w.set_damage_region(rects),
+            &Window::Wayland(ref w) => w.set_damage_region(rects)
+        }
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        match self {
+            &Window::X(ref w) => w.buffer_age(),
+            &Window::Wayland(ref w) => w.buffer_age()
+        }
+    }
+
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        match self {
+            &Window::X(ref w) => w.resize_surface(width, height),
+            &Window::Wayland(ref w) => w.resize_surface(width, height)
+        }
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        match self {
+            &Window::X(ref w) => w.get_reset_status(),
+            &Window::Wayland(ref w) => w.get_reset_status()
+        }
+    }
+
+    #[inline]
+    fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        match self {
+            &Window::X(ref w) => w.get_sync_values(),
+            &Window::Wayland(ref w) => w.get_sync_values()
+        }
+    }
+
+    #[inline]
+    fn swap_buffers_msc(&self, target_msc: i64, divisor: i64, remainder: i64)
+                        -> Result<i64, ContextError>
+    {
+        match self {
+            &Window::X(ref w) => w.swap_buffers_msc(target_msc, divisor, remainder),
+            &Window::Wayland(ref w) => w.swap_buffers_msc(target_msc, divisor, remainder)
+        }
+    }
+
+    #[inline]
+    fn wait_for_sbc(&self, target_sbc: i64) -> Result<SyncValues, ContextError> {
+        match self {
+            &Window::X(ref w) => w.wait_for_sbc(target_sbc),
+            &Window::Wayland(ref w) => w.wait_for_sbc(target_sbc)
+        }
+    }
+
     #[inline]
     fn get_api(&self) -> ::Api {
         match self {
@@ -100,6 +294,14 @@ impl GlContext for Window {
         }
     }
 
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        match self {
+            &Window::X(ref w) => w.get_api_version(),
+            &Window::Wayland(ref w) => w.get_api_version()
+        }
+    }
+
     #[inline]
     fn get_pixel_format(&self) -> PixelFormat {
         match self {
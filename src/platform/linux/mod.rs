@@ -58,6 +58,11 @@ impl GlContext for HeadlessContext {
         self.0.swap_buffers()
     }
 
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        self.0.resize_surface(width, height)
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         self.0.get_api()
@@ -14,6 +14,7 @@ use GlContext;
 use WindowAttributes;
 
 use winit;
+use window::build_winit_window;
 
 use api::egl::ffi::egl::Egl;
 use api::egl;
@@ -69,15 +70,20 @@ impl Window {
         pf_reqs: &PixelFormatRequirements,
         opengl: &GlAttributes<&Window>,
         _: &PlatformSpecificWindowBuilderAttributes,
-        winit_window: &winit::Window,
-    ) -> Result<Window, CreationError> {
-        win32::Window::new(
+        winit_window: Option<winit::Window>,
+    ) -> Result<(Window, winit::Window), CreationError> {
+        let winit_window = match winit_window {
+            Some(w) => w,
+            None => try!(build_winit_window(window, winit::WindowBuilder::new())),
+        };
+        let w = try!(win32::Window::new(
             window,
             pf_reqs,
             &opengl.clone().map_sharing(|w| &w.0),
             EGL.as_ref().map(|w| &w.0),
-            winit_window,
-        ).map(|w| Window(w))
+            &winit_window,
+        ));
+        Ok((Window(w), winit_window))
     }
 }
 
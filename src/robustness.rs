@@ -0,0 +1,88 @@
+//! Polling of the reset status of robust contexts with `glGetGraphicsResetStatus`.
+
+use std::mem;
+use std::os::raw::c_uint;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ContextError;
+use ResetStatus;
+use Robustness;
+
+type GetGraphicsResetStatusFn = extern "system" fn() -> c_uint;
+
+const NO_ERROR: c_uint = 0;
+const GUILTY_CONTEXT_RESET: c_uint = 0x8253;
+const INNOCENT_CONTEXT_RESET: c_uint = 0x8254;
+const UNKNOWN_CONTEXT_RESET: c_uint = 0x8255;
+
+/// Queries the reset status of a context and remembers it once a reset has been observed,
+/// since the driver stops reporting it after the reset has completed.
+pub struct ResetStatusQuery {
+    function: Option<GetGraphicsResetStatusFn>,
+    last_status: AtomicUsize,
+}
+
+impl ResetStatusQuery {
+    /// Loads `glGetGraphicsResetStatus` if `robustness` asks for a context that can be lost.
+    pub fn new<F>(robustness: Robustness, get_proc_address: F) -> ResetStatusQuery
+        where F: Fn(&str) -> *const ()
+    {
+        let function = match robustness {
+            Robustness::RobustLoseContextOnReset | Robustness::TryRobustLoseContextOnReset => {
+                ["glGetGraphicsResetStatus", "glGetGraphicsResetStatusARB",
+                 "glGetGraphicsResetStatusKHR", "glGetGraphicsResetStatusEXT"]
+                    .iter()
+                    .map(|name| get_proc_address(name))
+                    .find(|ptr| !ptr.is_null())
+                    .map(|ptr| unsafe { mem::transmute::<_, GetGraphicsResetStatusFn>(ptr) })
+            },
+            _ => None,
+        };
+
+        ResetStatusQuery {
+            function: function,
+            last_status: AtomicUsize::new(NO_ERROR as usize),
+        }
+    }
+
+    /// Returns the reset status of the context.
+    ///
+    /// The driver is only queried if `is_current` is true. Otherwise, or if the context isn't
+    /// robust, the last known status is returned.
+    pub fn get(&self, is_current: bool) -> ResetStatus {
+        let last = self.last_status.load(Ordering::SeqCst) as c_uint;
+        if last != NO_ERROR {
+            return to_reset_status(last);
+        }
+
+        let status = match self.function {
+            Some(function) if is_current => function(),
+            _ => NO_ERROR,
+        };
+
+        if status != NO_ERROR {
+            self.last_status.store(status as usize, Ordering::SeqCst);
+        }
+
+        to_reset_status(status)
+    }
+
+    /// Returns `Err(ContextError::ContextLost)` if the context has been reset.
+    #[inline]
+    pub fn check(&self, is_current: bool) -> Result<(), ContextError> {
+        match self.get(is_current) {
+            ResetStatus::NoError => Ok(()),
+            _ => Err(ContextError::ContextLost),
+        }
+    }
+}
+
+fn to_reset_status(status: c_uint) -> ResetStatus {
+    match status {
+        NO_ERROR => ResetStatus::NoError,
+        GUILTY_CONTEXT_RESET => ResetStatus::Guilty,
+        INNOCENT_CONTEXT_RESET => ResetStatus::Innocent,
+        UNKNOWN_CONTEXT_RESET => ResetStatus::Unknown,
+        _ => ResetStatus::Unknown,
+    }
+}
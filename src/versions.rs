@@ -0,0 +1,45 @@
+//! The versions that are tried, from the newest to the oldest, when the latest version of an API
+//! is requested.
+
+use Api;
+use GlProfile;
+
+const OPENGL_VERSIONS: &'static [(u8, u8)] = &[
+    (4, 6), (4, 5), (4, 4), (4, 3), (4, 2), (4, 1), (4, 0),
+    (3, 3), (3, 2), (3, 1), (3, 0),
+    (2, 1), (2, 0),
+    (1, 5), (1, 4), (1, 3), (1, 2), (1, 1), (1, 0),
+];
+
+const OPENGLES_VERSIONS: &'static [(u8, u8)] = &[
+    (3, 2), (3, 1), (3, 0),
+    (2, 0),
+    (1, 1), (1, 0),
+];
+
+/// Returns the versions of `api` that are at least `min_version` and that can be requested with
+/// `profile` and `forward_compatible`, from the newest to the oldest.
+///
+/// Profiles only exist since OpenGL 3.2 and forward-compatible contexts since OpenGL 3.0, so
+/// older versions are skipped if the core profile or a forward-compatible context is requested.
+pub fn latest(api: Api, min_version: (u8, u8), profile: Option<GlProfile>,
+              forward_compatible: bool) -> Vec<(u8, u8)>
+{
+    match api {
+        Api::OpenGl => {
+            OPENGL_VERSIONS.iter()
+                           .cloned()
+                           .filter(|&version| version >= min_version)
+                           .filter(|&version| profile != Some(GlProfile::Core) || version >= (3, 2))
+                           .filter(|&version| !forward_compatible || version >= (3, 0))
+                           .collect()
+        },
+        Api::OpenGlEs => {
+            OPENGLES_VERSIONS.iter()
+                             .cloned()
+                             .filter(|&version| version >= min_version)
+                             .collect()
+        },
+        Api::WebGl => Vec::new(),
+    }
+}
@@ -1,23 +1,26 @@
+use std::cell::Cell;
 use std::default::Default;
 
 use Api;
+use Backend;
 use ContextError;
 use CreationError;
+use Event;
 use GlContext;
-use GlProfile;
-use GlRequest;
 use PixelFormat;
-use Robustness;
+use Rect;
+use ResetStatus;
+use SyncValues;
 use Window;
+use WindowAttributes;
 use WindowBuilder;
 
 pub use winit::WindowProxy;
-pub use winit::PollEventsIterator;
-pub use winit::WaitEventsIterator;
 pub use winit::{AvailableMonitorsIter};
 pub use winit::{get_primary_monitor, get_available_monitors};
 pub use winit::{MonitorId};
 
+use debug::DebugOutputState;
 use libc;
 use platform;
 
@@ -36,6 +39,9 @@ impl<'a> WindowBuilder<'a> {
         }
     }
 
+    /// Builds the context for an existing winit window instead of creating a new one.
+    ///
+    /// The window attributes of this builder are then ignored.
     #[inline]
     pub fn with_winit_window(mut self, winit_window: winit::Window) -> WindowBuilder<'a> {
         self.winit_window = Some(winit_window);
@@ -97,43 +103,7 @@ impl<'a> WindowBuilder<'a> {
         self
     }
 
-    /// Sets how the backend should choose the OpenGL API and version.
-    #[inline]
-    pub fn with_gl(mut self, request: GlRequest) -> WindowBuilder<'a> {
-        self.opengl.version = request;
-        self
-    }
-
-    /// Sets the desired OpenGL context profile.
-    #[inline]
-    pub fn with_gl_profile(mut self, profile: GlProfile) -> WindowBuilder<'a> {
-        self.opengl.profile = Some(profile);
-        self
-    }
-
-    /// Sets the *debug* flag for the OpenGL context.
-    ///
-    /// The default value for this flag is `cfg!(debug_assertions)`, which means that it's enabled
-    /// when you run `cargo build` and disabled when you run `cargo build --release`.
-    #[inline]
-    pub fn with_gl_debug_flag(mut self, flag: bool) -> WindowBuilder<'a> {
-        self.opengl.debug = flag;
-        self
-    }
-
-    /// Sets the robustness of the OpenGL context. See the docs of `Robustness`.
-    #[inline]
-    pub fn with_gl_robustness(mut self, robustness: Robustness) -> WindowBuilder<'a> {
-        self.opengl.robustness = robustness;
-        self
-    }
-
-    /// Requests that the window has vsync enabled.
-    #[inline]
-    pub fn with_vsync(mut self) -> WindowBuilder<'a> {
-        self.opengl.vsync = true;
-        self
-    }
+    context_builder_setters!(WindowBuilder<'a>);
 
     /// Sets whether the window will be initially hidden or visible.
     #[inline]
@@ -142,55 +112,11 @@ impl<'a> WindowBuilder<'a> {
         self
     }
 
-    /// Sets the multisampling level to request.
-    ///
-    /// # Panic
-    ///
-    /// Will panic if `samples` is not a power of two.
-    #[inline]
-    pub fn with_multisampling(mut self, samples: u16) -> WindowBuilder<'a> {
-        assert!(samples.is_power_of_two());
-        self.pf_reqs.multisampling = Some(samples);
-        self
-    }
-
-    /// Sets the number of bits in the depth buffer.
-    #[inline]
-    pub fn with_depth_buffer(mut self, bits: u8) -> WindowBuilder<'a> {
-        self.pf_reqs.depth_bits = Some(bits);
-        self
-    }
-
-    /// Sets the number of bits in the stencil buffer.
-    #[inline]
-    pub fn with_stencil_buffer(mut self, bits: u8) -> WindowBuilder<'a> {
-        self.pf_reqs.stencil_bits = Some(bits);
-        self
-    }
-
-    /// Sets the number of bits in the color buffer.
-    #[inline]
-    pub fn with_pixel_format(mut self, color_bits: u8, alpha_bits: u8) -> WindowBuilder<'a> {
-        self.pf_reqs.color_bits = Some(color_bits);
-        self.pf_reqs.alpha_bits = Some(alpha_bits);
-        self
-    }
-
-    /// Request the backend to be stereoscopic.
-    #[inline]
-    pub fn with_stereoscopy(mut self) -> WindowBuilder<'a> {
-        self.pf_reqs.stereoscopy = true;
-        self
-    }
-
-    /// Sets whether sRGB should be enabled on the window. `None` means "I don't care".
-    #[inline]
-    pub fn with_srgb(mut self, srgb_enabled: Option<bool>) -> WindowBuilder<'a> {
-        self.pf_reqs.srgb = srgb_enabled.unwrap_or(false);
-        self
-    }
-
     /// Sets whether the background of the window should be transparent.
+    ///
+    /// This requires a config with an alpha channel, whose visual has one on X11. If there is
+    /// none, the window is opaque, or `build_strict` returns an error. On Wayland, windows that
+    /// aren't transparent are marked as opaque so that the compositor doesn't blend them.
     #[inline]
     pub fn with_transparency(mut self, transparent: bool) -> WindowBuilder<'a> {
         self.window.transparent = transparent;
@@ -226,29 +152,30 @@ impl<'a> WindowBuilder<'a> {
             self.window.dimensions = Some((1024, 768));
         }
 
-        let winit_window = match self.winit_window {
-            None => {
-                match winit::Window::new() {
-                    Ok(w) => w,
-                    Err(_) => panic!(),
-                }
-            },
-            Some(w) => w,
-        };
-
-        // building
-        let w = try!(platform::Window::new(
+        // building; the window is created by the platform if none was given, so that it can
+        // be created with attributes that match the context
+        let (w, winit_window) = try!(platform::Window::new(
             &self.window,
             &self.pf_reqs,
             &self.opengl,
             &self.platform_specific,
-            &winit_window,
+            self.winit_window,
         ));
         // let ozkriff_w = try!(winit::Window::new(&self.window, &self.platform_specific));
 
+        let debug_output = if self.opengl.debug {
+            self.opengl.debug_output.take().map(DebugOutputState::new)
+        } else {
+            None
+        };
+
+        let buffer_scale = buffer_scale(winit_window.hidpi_factor());
+
         Result::Ok(Window {
             window: w,
             winit_window: winit_window,
+            debug_output: debug_output,
+            buffer_scale: Cell::new(buffer_scale),
         })
     }
 
@@ -257,7 +184,8 @@ impl<'a> WindowBuilder<'a> {
     /// The context is build in a *strict* way. That means that if the backend couldn't give
     /// you what you requested, an `Err` will be returned.
     #[inline]
-    pub fn build_strict(self) -> Result<Window, CreationError> {
+    pub fn build_strict(mut self) -> Result<Window, CreationError> {
+        self.opengl.strict = true;
         self.build()
     }
 }
@@ -397,7 +325,42 @@ impl Window {
     /// This is a no-op if the window has already been closed.
     #[inline]
     pub fn set_inner_size(&self, x: u32, y: u32) {
-        self.winit_window.set_inner_size(x, y)
+        self.winit_window.set_inner_size(x, y);
+        self.update_surface();
+    }
+
+    /// Resizes the surface that the context renders to. See `GlContext::resize_surface`.
+    ///
+    /// This is done automatically by `set_inner_size` and by the events iterators, so you only
+    /// need it if you process the events of the window yourself.
+    #[inline]
+    pub fn resize_surface(&self, width: u32, height: u32) {
+        self.window.resize_surface(width, height)
+    }
+
+    /// Brings the scale and the size of the surface in line with the window, so that the
+    /// drawable always matches `get_inner_size_pixels`.
+    fn update_surface(&self) {
+        let scale = buffer_scale(self.hidpi_factor());
+        if scale != self.buffer_scale.get() {
+            set_buffer_scale(&self.window, scale);
+            self.buffer_scale.set(scale);
+        }
+
+        if let Some((width, height)) = self.get_inner_size_pixels() {
+            self.window.resize_surface(width, height);
+        }
+    }
+
+    /// Called by the events iterators for each event that goes through them.
+    fn handle_event(&self, event: &Event) {
+        match *event {
+            Event::Resized(..) => self.update_surface(),
+            _ if buffer_scale(self.hidpi_factor()) != self.buffer_scale.get() => {
+                self.update_surface()
+            },
+            _ => (),
+        }
     }
 
     /// Returns an iterator that poll for the next event in the window's events queue.
@@ -405,8 +368,11 @@ impl Window {
     ///
     /// Contrary to `wait_events`, this function never blocks.
     #[inline]
-    pub fn poll_events(&self) -> winit::PollEventsIterator {
-        self.winit_window.poll_events()
+    pub fn poll_events(&self) -> PollEventsIterator {
+        PollEventsIterator {
+            window: self,
+            events: self.winit_window.poll_events(),
+        }
     }
 
     /// Returns an iterator that returns events one by one, blocking if necessary until one is
@@ -414,14 +380,23 @@ impl Window {
     ///
     /// The iterator never returns `None`.
     #[inline]
-    pub fn wait_events(&self) -> winit::WaitEventsIterator {
-        self.winit_window.wait_events()
+    pub fn wait_events(&self) -> WaitEventsIterator {
+        WaitEventsIterator {
+            window: self,
+            events: self.winit_window.wait_events(),
+        }
     }
 
     /// Sets the context as the current context.
     #[inline]
     pub unsafe fn make_current(&self) -> Result<(), ContextError> {
-        self.window.make_current()
+        try!(self.window.make_current());
+
+        if let Some(ref debug_output) = self.debug_output {
+            debug_output.install_if_needed(|addr| self.window.get_proc_address(addr));
+        }
+
+        Ok(())
     }
 
     /// Returns true if this context is the current one in this thread.
@@ -451,6 +426,64 @@ impl Window {
         self.window.swap_buffers()
     }
 
+    /// Swaps the buffers, telling the system that only the regions in `rects` have changed
+    /// since the previous frame.
+    ///
+    /// On Wayland this lets the compositor only recompose the damaged regions. Falls back to
+    /// `swap_buffers` if the backend doesn't support it.
+    #[inline]
+    pub fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.window.swap_buffers_with_damage(rects)
+    }
+
+    /// Tells the backend which regions of the back buffer are going to be redrawn during the
+    /// current frame. See `GlContext::set_damage_region`.
+    #[inline]
+    pub fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.window.set_damage_region(rects)
+    }
+
+    /// Returns the age of the contents of the current back buffer, in frames, or `None` if the
+    /// backend can't tell. See `GlContext::buffer_age`.
+    #[inline]
+    pub fn buffer_age(&self) -> Option<u32> {
+        self.window.buffer_age()
+    }
+
+    /// Returns whether the context has been reset. See `GlContext::get_reset_status`.
+    #[inline]
+    pub fn get_reset_status(&self) -> ResetStatus {
+        self.window.get_reset_status()
+    }
+
+    /// Returns the current values of the presentation counters of the window.
+    /// See `GlContext::get_sync_values`.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - Only supported with GLX, through `GLX_OML_sync_control`.
+    ///
+    #[inline]
+    pub fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        self.window.get_sync_values()
+    }
+
+    /// Schedules a buffer swap for a given media stream counter value, without blocking.
+    /// See `GlContext::swap_buffers_msc`.
+    #[inline]
+    pub fn swap_buffers_msc(&self, target_msc: i64, divisor: i64, remainder: i64)
+                            -> Result<i64, ContextError>
+    {
+        self.window.swap_buffers_msc(target_msc, divisor, remainder)
+    }
+
+    /// Blocks until the swap buffer counter of the window reaches `target_sbc`.
+    /// See `GlContext::wait_for_sbc`.
+    #[inline]
+    pub fn wait_for_sbc(&self, target_sbc: i64) -> Result<SyncValues, ContextError> {
+        self.window.wait_for_sbc(target_sbc)
+    }
+
     /// DEPRECATED. Gets the native platform specific display for this window.
     /// This is typically only required when integrating with
     /// other libraries that need this information.
@@ -477,6 +510,13 @@ impl Window {
         self.window.get_api()
     }
 
+    /// Returns the version of the API that was picked when the context was created, or `None`
+    /// if it is unknown. See `GlContext::get_api_version`.
+    #[inline]
+    pub fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.window.get_api_version()
+    }
+
     /// Returns the pixel format of this window.
     #[inline]
     pub fn get_pixel_format(&self) -> PixelFormat {
@@ -527,6 +567,84 @@ impl Window {
     }
 }
 
+/// An iterator for the `poll_events` function.
+///
+/// The surface of the context is resized when an `Event::Resized` goes through it, and is
+/// rescaled when the hidpi factor of the window changes.
+pub struct PollEventsIterator<'a> {
+    window: &'a Window,
+    events: winit::PollEventsIterator<'a>,
+}
+
+impl<'a> Iterator for PollEventsIterator<'a> {
+    type Item = Event;
+
+    #[inline]
+    fn next(&mut self) -> Option<Event> {
+        let event = self.events.next();
+        if let Some(ref event) = event {
+            self.window.handle_event(event);
+        }
+        event
+    }
+}
+
+/// An iterator for the `wait_events` function.
+///
+/// The surface of the context is resized when an `Event::Resized` goes through it, and is
+/// rescaled when the hidpi factor of the window changes.
+pub struct WaitEventsIterator<'a> {
+    window: &'a Window,
+    events: winit::WaitEventsIterator<'a>,
+}
+
+impl<'a> Iterator for WaitEventsIterator<'a> {
+    type Item = Event;
+
+    #[inline]
+    fn next(&mut self) -> Option<Event> {
+        let event = self.events.next();
+        if let Some(ref event) = event {
+            self.window.handle_event(event);
+        }
+        event
+    }
+}
+
+/// Turns a hidpi factor into the integer scale of a surface's buffer, which is at least 1.
+#[inline]
+pub fn buffer_scale(hidpi_factor: f32) -> u32 {
+    if hidpi_factor < 1.0 { 1 } else { hidpi_factor.round() as u32 }
+}
+
+/// Tells the compositor by how much the surface of `window` is scaled, which only Wayland needs
+/// to not upscale what we render on HiDPI outputs.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "openbsd"))]
+#[inline]
+fn set_buffer_scale(window: &platform::Window, scale: u32) {
+    window.set_buffer_scale(scale)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "openbsd")))]
+#[inline]
+fn set_buffer_scale(_: &platform::Window, _: u32) {
+}
+
+/// Builds a winit window with the attributes of `window`, for the platforms that create it
+/// themselves. `builder` holds the platform-specific attributes of the winit window.
+pub fn build_winit_window(window: &WindowAttributes, mut builder: winit::WindowBuilder)
+                          -> Result<winit::Window, CreationError>
+{
+    builder.window = window.clone();
+    builder.build().map_err(|err| CreationError::BackendError {
+        backend: Backend::Winit,
+        code: None,
+        message: format!("Failed to create the window: {}", err),
+    })
+}
+
 /*
 unsafe fn make_current(winit_window: &winit::Window) -> Result<(), ContextError> {
     // self.make_current()
@@ -569,11 +687,58 @@ impl GlContext for Window {
         self.swap_buffers()
     }
 
+    #[inline]
+    fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.swap_buffers_with_damage(rects)
+    }
+
+    #[inline]
+    fn set_damage_region(&self, rects: &[Rect]) -> Result<(), ContextError> {
+        self.set_damage_region(rects)
+    }
+
+    #[inline]
+    fn buffer_age(&self) -> Option<u32> {
+        self.buffer_age()
+    }
+
+    #[inline]
+    fn resize_surface(&self, width: u32, height: u32) {
+        self.resize_surface(width, height)
+    }
+
+    #[inline]
+    fn get_reset_status(&self) -> ResetStatus {
+        self.get_reset_status()
+    }
+
+    #[inline]
+    fn get_sync_values(&self) -> Result<SyncValues, ContextError> {
+        self.get_sync_values()
+    }
+
+    #[inline]
+    fn swap_buffers_msc(&self, target_msc: i64, divisor: i64, remainder: i64)
+                        -> Result<i64, ContextError>
+    {
+        self.swap_buffers_msc(target_msc, divisor, remainder)
+    }
+
+    #[inline]
+    fn wait_for_sbc(&self, target_sbc: i64) -> Result<SyncValues, ContextError> {
+        self.wait_for_sbc(target_sbc)
+    }
+
     #[inline]
     fn get_api(&self) -> Api {
         self.get_api()
     }
 
+    #[inline]
+    fn get_api_version(&self) -> Option<(u8, u8)> {
+        self.get_api_version()
+    }
+
     #[inline]
     fn get_pixel_format(&self) -> PixelFormat {
         self.get_pixel_format()
@@ -0,0 +1,71 @@
+extern crate glutin;
+
+use glutin::{Api, Backend, CreationAttempt, CreationError, GlProfile};
+
+#[test]
+fn attempts_are_listed() {
+    let err = CreationError::AttemptsFailed(vec![
+        CreationAttempt {
+            backend: Backend::Glx,
+            library: Some("libGL.so.1".to_owned()),
+            version: Some((Api::OpenGl, (4, 6))),
+            profile: Some(GlProfile::Core),
+            config_id: Some(0x2a),
+            error: CreationError::OpenGlVersionNotSupported,
+        },
+        CreationAttempt {
+            backend: Backend::Egl,
+            library: None,
+            version: None,
+            profile: None,
+            config_id: None,
+            error: CreationError::NoAvailablePixelFormat,
+        },
+    ]);
+
+    let text = format!("{}", err);
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1], "  - Glx (libGL.so.1), OpenGl 4.6, Core profile, config 0x2a: \
+                          The requested OpenGL version is not supported.");
+    assert!(lines[2].starts_with("  - Egl: "));
+}
+
+// the libraries of the environment variables can't be loaded, so every attempt must record the
+// system library that was used instead, or none
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+#[test]
+fn attempts_record_the_loaded_library() {
+    use glutin::GlRequest;
+
+    const MISSING_GLX: &'static str = "/nonexistent/libGL.so.1";
+    const MISSING_EGL: &'static str = "/nonexistent/libEGL.so.1";
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() && std::env::var_os("DISPLAY").is_none() {
+        return;
+    }
+
+    std::env::set_var("GLUTIN_GLX_LIBRARY", MISSING_GLX);
+    std::env::set_var("GLUTIN_EGL_LIBRARY", MISSING_EGL);
+
+    // no implementation provides this version, so every backend fails
+    let result = glutin::WindowBuilder::new()
+        .with_visibility(false)
+        .with_gl(GlRequest::Specific(Api::OpenGl, (99, 0)))
+        .build();
+
+    let attempts = match result {
+        Err(CreationError::AttemptsFailed(attempts)) => attempts,
+        Err(_) => return,
+        Ok(_) => panic!("Created a context for OpenGL 99.0"),
+    };
+
+    assert!(!attempts.is_empty());
+    for attempt in &attempts {
+        match attempt.backend {
+            Backend::Glx => assert!(attempt.library.as_ref().map(|l| &l[..]) != Some(MISSING_GLX)),
+            Backend::Egl => assert!(attempt.library.as_ref().map(|l| &l[..]) != Some(MISSING_EGL)),
+            backend => panic!("Unexpected backend {:?}", backend),
+        }
+    }
+}
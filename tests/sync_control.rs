@@ -0,0 +1,46 @@
+extern crate glutin;
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+#[test]
+#[ignore = "needs an X server whose GLX implementation supports GLX_OML_sync_control"]
+fn sync_control_counters() {
+    let window = glutin::WindowBuilder::new().build().unwrap();
+    unsafe { window.make_current().expect("Couldn't make window current") };
+
+    let before = window.get_sync_values().unwrap();
+
+    let sbc = window.swap_buffers_msc(0, 0, 0).unwrap();
+    assert!(sbc > before.sbc);
+
+    let after = window.wait_for_sbc(sbc).unwrap();
+    assert!(after.sbc >= sbc);
+    assert!(after.msc >= before.msc);
+    assert!(after.ust >= before.ust);
+}
+
+// runs without a display server, and only needs OSMesa to create the context
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+#[test]
+fn headless_sync_control_is_unavailable() {
+    use glutin::{ContextError, GlContext};
+
+    let context = match glutin::HeadlessRendererBuilder::new(64, 64).build() {
+        Ok(context) => context,
+        // OSMesa isn't installed
+        Err(_) => return,
+    };
+    unsafe { context.make_current().expect("Couldn't make context current") };
+
+    match context.get_sync_values() {
+        Err(ContextError::FunctionUnavailable) => (),
+        other => panic!("Expected FunctionUnavailable, got {:?}", other),
+    }
+    match context.swap_buffers_msc(0, 0, 0) {
+        Err(ContextError::FunctionUnavailable) => (),
+        other => panic!("Expected FunctionUnavailable, got {:?}", other),
+    }
+    match context.wait_for_sbc(0) {
+        Err(ContextError::FunctionUnavailable) => (),
+        other => panic!("Expected FunctionUnavailable, got {:?}", other),
+    }
+}
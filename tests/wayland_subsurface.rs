@@ -0,0 +1,57 @@
+extern crate glutin;
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+#[test]
+#[ignore = "needs a Wayland compositor that supports wl_subcompositor"]
+fn dropping_subsurface_keeps_parent_usable() {
+    use glutin::GlContext;
+    use glutin::os::unix::WindowExt;
+
+    let window = glutin::WindowBuilder::new().build().unwrap();
+
+    {
+        let subsurface = window.create_subsurface(64, 64, &Default::default()).unwrap();
+        unsafe { subsurface.make_current().expect("Couldn't make subsurface current") };
+        subsurface.swap_buffers().unwrap();
+    }
+
+    // the subsurface and the window share their EGL display, which must still be alive
+    unsafe { window.make_current().expect("Couldn't make window current") };
+    window.swap_buffers().unwrap();
+}
+
+// runs wherever a window can be created, and only checks the subsurface on Wayland
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+#[test]
+fn subsurface_gets_requested_alpha() {
+    use glutin::GlContext;
+    use glutin::os::unix::{UnixBackend, WindowExt};
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() && std::env::var_os("DISPLAY").is_none() {
+        return;
+    }
+
+    let window = match glutin::WindowBuilder::new().with_visibility(false).build() {
+        Ok(window) => window,
+        Err(_) => return,
+    };
+
+    let mut pf_reqs = glutin::PixelFormatRequirements::default();
+    pf_reqs.alpha_bits = Some(8);
+    let result = window.create_subsurface(64, 64, &pf_reqs);
+
+    if window.get_unix_backend() != UnixBackend::Wayland {
+        match result {
+            Err(glutin::CreationError::NotSupported) => (),
+            _ => panic!("Subsurfaces are only supported on Wayland"),
+        }
+        return;
+    }
+
+    match result {
+        Ok(subsurface) => assert!(subsurface.get_pixel_format().alpha_bits >= 8),
+        // the compositor doesn't support subsurfaces
+        Err(glutin::CreationError::NotSupported) => (),
+        Err(err) => panic!("{}", err),
+    }
+}